@@ -0,0 +1,265 @@
+use errors::*;
+use fetch::read_file_into_string;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml;
+
+// name of the site described by the top-level settings
+const DEFAULT_SITE_NAME: &str = "default";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ModConfig {
+    pub update_center_url: String,
+    /// Overrides the JSONP envelope written back, which is otherwise the
+    /// one detected in the upstream response.
+    pub suppress_front: Option<String>,
+    pub suppress_back: Option<String>,
+    pub connection_check_url_change: String,
+    pub url_replace_from: String,
+    pub url_replace_into: String,
+    #[serde(default)]
+    pub url_rewrite_rules: Vec<UrlRewriteRuleConfig>,
+    #[serde(default)]
+    pub apply_all_url_rewrite_rules: bool,
+    #[serde(default)]
+    pub write_url_rewrite_report: bool,
+    pub auto_create_output_dir: bool,
+    pub modified_json_file_path: PathBuf,
+    pub url_list_json_file_path: PathBuf,
+    #[serde(default)]
+    pub write_actual_json: bool,
+    #[serde(default)]
+    pub write_gzip_copies: bool,
+    pub state_file_path: Option<PathBuf>,
+    /// Keeps the modified JSON files here until `jenkins-sync` has every
+    /// artifact they refer to and promotes them.
+    pub staging_dir_path: Option<PathBuf>,
+    pub retry: Option<RetryConfig>,
+    #[serde(default)]
+    pub plugin_includes: Vec<String>,
+    #[serde(default)]
+    pub plugin_excludes: Vec<String>,
+    #[serde(default)]
+    pub plugin_roots: Vec<String>,
+    #[serde(default)]
+    pub include_optional_dependencies: bool,
+    #[serde(default)]
+    pub strict_dependency_check: bool,
+    pub target_core_version: Option<String>,
+    /// LTS line, such as `2.401`, standing for the release of its
+    /// update-center, instead of `target_core_version`.
+    pub target_core_line: Option<String>,
+    /// Update-center of each LTS line, with `{line}` standing for the line,
+    /// for resolving a target core line into its release.
+    pub stable_update_center_url: Option<String>,
+    #[serde(default)]
+    pub keep_incompatible_plugins: bool,
+    #[serde(default)]
+    pub check_security_warnings: bool,
+    #[serde(default)]
+    pub keep_vulnerable_plugins: bool,
+    #[serde(default)]
+    pub core_variants: Vec<CoreVariantConfig>,
+    pub historical_versions: Option<HistoricalVersionsConfig>,
+    #[serde(default)]
+    pub sites: Vec<SiteConfig>,
+    pub signing: Option<SigningConfig>,
+    pub verification: Option<VerificationConfig>,
+}
+
+impl ModConfig {
+    /// The top-level site settings form the default site, followed by any
+    /// extra sites.
+    pub fn all_sites(&self) -> Vec<SiteConfig> {
+        let default_site = SiteConfig {
+            name: DEFAULT_SITE_NAME.to_owned(),
+            update_center_url: self.update_center_url.to_owned(),
+            suppress_front: self.suppress_front.clone(),
+            suppress_back: self.suppress_back.clone(),
+            connection_check_url_change: self.connection_check_url_change.to_owned(),
+            url_replace_from: self.url_replace_from.to_owned(),
+            url_replace_into: self.url_replace_into.to_owned(),
+            url_rewrite_rules: self.url_rewrite_rules.to_vec(),
+            apply_all_url_rewrite_rules: self.apply_all_url_rewrite_rules,
+            modified_json_file_path: self.modified_json_file_path.to_owned(),
+        };
+
+        let mut sites = vec![default_site];
+        sites.extend(self.sites.iter().cloned());
+        sites
+    }
+
+    pub fn core_target(&self) -> Result<Option<CoreTarget<'_>>> {
+        core_target(
+            self.target_core_version.as_deref(),
+            self.target_core_line.as_deref(),
+        )
+    }
+}
+
+/// The core to filter plugins against and pin, either an exact release or
+/// an LTS line standing for the release of its update-center.
+#[derive(Debug, Clone, Copy)]
+pub enum CoreTarget<'a> {
+    Version(&'a str),
+    Line(&'a str),
+}
+
+impl<'a> fmt::Display for CoreTarget<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CoreTarget::Version(version) => write!(f, "{}", version),
+            CoreTarget::Line(line) => write!(f, "line {}", line),
+        }
+    }
+}
+
+fn core_target<'a>(
+    target_core_version: Option<&'a str>,
+    target_core_line: Option<&'a str>,
+) -> Result<Option<CoreTarget<'a>>> {
+    match (target_core_version, target_core_line) {
+        (Some(_), Some(_)) => {
+            bail!("Expected only one of 'target_core_version' and 'target_core_line'")
+        }
+        (Some(version), None) => Ok(Some(CoreTarget::Version(version))),
+        (None, Some(line)) => Ok(Some(CoreTarget::Line(line))),
+        (None, None) => Ok(None),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SiteConfig {
+    pub name: String,
+    pub update_center_url: String,
+    pub suppress_front: Option<String>,
+    pub suppress_back: Option<String>,
+    pub connection_check_url_change: String,
+    pub url_replace_from: String,
+    pub url_replace_into: String,
+    #[serde(default)]
+    pub url_rewrite_rules: Vec<UrlRewriteRuleConfig>,
+    #[serde(default)]
+    pub apply_all_url_rewrite_rules: bool,
+    pub modified_json_file_path: PathBuf,
+}
+
+/// Regex rewrite of URLs, where `replacement` may refer to capture groups
+/// as `$1` or `${name}`. `scope` is one of `core`, `plugins`, `warnings`,
+/// `wiki`, `scm`, `issue_trackers`, `deprecations` or `all`, defaulting
+/// to `all`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UrlRewriteRuleConfig {
+    pub pattern: String,
+    pub replacement: String,
+    pub scope: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CoreVariantConfig {
+    pub target_core_version: Option<String>,
+    pub target_core_line: Option<String>,
+    pub output_dir_path: PathBuf,
+}
+
+impl CoreVariantConfig {
+    pub fn core_target(&self) -> Result<CoreTarget<'_>> {
+        let core_target = core_target(
+            self.target_core_version.as_deref(),
+            self.target_core_line.as_deref(),
+        )?;
+
+        match core_target {
+            Some(core_target) => Ok(core_target),
+            None => bail!(format!(
+                "Expected one of 'target_core_version' and 'target_core_line' in core variant {:?}",
+                self.output_dir_path
+            )),
+        }
+    }
+}
+
+/// When both `version_count` and `max_age_days` are set, a version must
+/// satisfy both to be mirrored.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HistoricalVersionsConfig {
+    pub plugin_versions_url: String,
+    pub version_count: Option<usize>,
+    pub max_age_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SigningConfig {
+    pub private_key_file_path: PathBuf,
+    pub certificate_file_path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VerificationConfig {
+    pub root_ca_file_path: PathBuf,
+}
+
+/// Retries of connection failures, 5xx, 408 and 429 responses, with the
+/// delay doubling from `initial_delay_ms` up to `max_delay_ms`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetryConfig {
+    /// Includes the first attempt.
+    pub max_attempts: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncConfig {
+    pub sync_root_dir_path: String,
+    /// Only optional in `CombinedConfig`, where the URL list may come
+    /// straight from the mod step instead.
+    pub url_list_json_file_path: Option<PathBuf>,
+    pub accepted_file_exts: Vec<String>,
+    pub download_thread_count: u32,
+    /// Same as the `staging_dir_path` of `ModConfig`, promoting what was
+    /// staged there once every download has succeeded.
+    pub staging_dir_path: Option<PathBuf>,
+    pub generations: Option<GenerationsConfig>,
+    pub retry: Option<RetryConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GenerationsConfig {
+    /// Includes the current generation.
+    pub keep_count: usize,
+}
+
+/// Single config for both steps, with the mod settings under `[mod]` and
+/// the sync settings under `[sync]`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CombinedConfig {
+    #[serde(rename = "mod")]
+    pub mod_config: ModConfig,
+    pub sync: SyncConfig,
+}
+
+impl CombinedConfig {
+    /// The URL list read by the sync step, which defaults to the one written
+    /// by the mod step.
+    pub fn url_list_json_file_path(&self) -> &Path {
+        match self.sync.url_list_json_file_path {
+            Some(ref url_list_json_file_path) => url_list_json_file_path,
+            None => &self.mod_config.url_list_json_file_path,
+        }
+    }
+}
+
+/// Reads and parses a TOML config file, such as `ModConfig`, `SyncConfig` or
+/// `CombinedConfig`.
+pub fn read_config<T: DeserializeOwned>(config_path: &Path) -> Result<T> {
+    let config_str = read_file_into_string(config_path)?;
+
+    toml::from_str(&config_str).chain_err(|| {
+        format!(
+            "Unable to parse config as required toml format: {}",
+            config_str
+        )
+    })
+}
@@ -0,0 +1,204 @@
+use errors::*;
+use hyper::client::pool::Pool;
+use hyper::client::{Client, RedirectPolicy, Response};
+use hyper::header::{
+    ETag, EntityTag, Headers, HttpDate, IfModifiedSince, IfNoneMatch, IfRange, LastModified,
+};
+use hyper::net::HttpsConnector;
+use hyper::status::StatusCode;
+use retry::{io_error, send_request};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+use tls::OpensslClient;
+use url::Url;
+
+/// Path standing for stdin when reading and stdout when writing.
+pub const STDIO_PATH: &str = "-";
+
+const FILE_SCHEME: &str = "file";
+
+// a stalled connection fails, and is retried, rather than hanging the run
+const SOCKET_TIMEOUT_SECS: u64 = 60;
+
+/// HTTP and HTTPS client following every redirect, as mirrors commonly
+/// redirect downloads elsewhere, and timing out on stalled reads and writes.
+pub fn new_client() -> Result<Client> {
+    let ssl = OpensslClient::new().chain_err(|| "Unable to set up TLS for HTTPS requests")?;
+    let connector = Pool::with_connector(Default::default(), HttpsConnector::new(ssl));
+
+    let mut client = Client::with_connector(connector);
+    client.set_redirect_policy(RedirectPolicy::FollowAll);
+    client.set_read_timeout(Some(Duration::from_secs(SOCKET_TIMEOUT_SECS)));
+    client.set_write_timeout(Some(Duration::from_secs(SOCKET_TIMEOUT_SECS)));
+    Ok(client)
+}
+
+/// Reads `url` over HTTP, or from a local file if it is a `file://` URL, or
+/// from stdin if it is `-`, so that the update-center can be carried over
+/// into networks without access to it.
+pub fn fetch_string(client: &Client, url: &str) -> Result<String> {
+    if url == STDIO_PATH {
+        let mut resp_str = String::new();
+
+        io::stdin()
+            .read_to_string(&mut resp_str)
+            .chain_err(|| "Unable to read stdin into string")?;
+
+        return Ok(resp_str);
+    }
+
+    if let Ok(file_url) = Url::parse(url) {
+        if file_url.scheme() == FILE_SCHEME {
+            let path = match file_url.to_file_path() {
+                Ok(path) => path,
+                Err(_) => bail!(format!("Unable to convert URL '{}' into file path", url)),
+            };
+
+            return read_file_into_string(&path);
+        }
+    }
+
+    let resp = send_request(client.get(url), url)?;
+    check_success(&resp, url)?;
+    read_resp_into_string(resp, url)
+}
+
+/// `ETag` and `Last-Modified` of a response, sent back as `If-None-Match`
+/// and `If-Modified-Since` on the next request.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &Headers) -> Validators {
+        Validators {
+            etag: headers.get::<ETag>().map(|etag| etag.0.to_string()),
+            last_modified: headers
+                .get::<LastModified>()
+                .map(|last_modified| last_modified.0.to_string()),
+        }
+    }
+
+    /// `If-Range` for resuming a partial response, preferring a strong ETag
+    /// as weak ones are not allowed there.
+    pub fn if_range(&self) -> Option<IfRange> {
+        let etag = self
+            .etag
+            .as_ref()
+            .and_then(|etag| etag.parse::<EntityTag>().ok())
+            .filter(|etag| !etag.weak);
+
+        match etag {
+            Some(etag) => Some(IfRange::EntityTag(etag)),
+            None => self
+                .last_modified
+                .as_ref()
+                .and_then(|last_modified| last_modified.parse::<HttpDate>().ok())
+                .map(IfRange::Date),
+        }
+    }
+}
+
+pub enum ConditionalFetch {
+    NotModified,
+    Modified(String, Validators),
+}
+
+/// Like `fetch_string`, but lets the server answer that nothing changed
+/// since the response `validators` were taken from.
+pub fn fetch_string_if_modified(
+    client: &Client,
+    url: &str,
+    validators: &Validators,
+) -> Result<ConditionalFetch> {
+    // local inputs have no validators and are always read in full
+    let is_local = url == STDIO_PATH
+        || Url::parse(url)
+            .map(|url| url.scheme() == FILE_SCHEME)
+            .unwrap_or(false);
+
+    if is_local {
+        return fetch_string(client, url)
+            .map(|resp_str| ConditionalFetch::Modified(resp_str, Validators::default()));
+    }
+
+    let mut headers = Headers::new();
+
+    if let Some(ref etag) = validators.etag {
+        match etag.parse::<EntityTag>() {
+            Ok(etag) => headers.set(IfNoneMatch::Items(vec![etag])),
+            Err(_) => warn!("Ignoring invalid stored ETag '{}'", etag),
+        }
+    }
+
+    if let Some(ref last_modified) = validators.last_modified {
+        match last_modified.parse::<HttpDate>() {
+            Ok(last_modified) => headers.set(IfModifiedSince(last_modified)),
+            Err(_) => warn!("Ignoring invalid stored Last-Modified '{}'", last_modified),
+        }
+    }
+
+    let resp = send_request(client.get(url).headers(headers), url)?;
+
+    if resp.status == StatusCode::NotModified {
+        return Ok(ConditionalFetch::NotModified);
+    }
+
+    check_success(&resp, url)?;
+
+    let validators = Validators::from_headers(&resp.headers);
+    let resp_str = read_resp_into_string(resp, url)?;
+    Ok(ConditionalFetch::Modified(resp_str, validators))
+}
+
+// anything else carries an error page rather than the content asked for
+fn check_success(resp: &Response, url: &str) -> Result<()> {
+    if !resp.status.is_success() {
+        bail!(format!(
+            "HTTP request with URL '{}' failed with {}",
+            url,
+            resp.status
+        ));
+    }
+
+    Ok(())
+}
+
+// a connection dropped midway is worth retrying
+fn read_resp_into_string(mut resp: Response, url: &str) -> Result<String> {
+    let mut resp_str = String::new();
+
+    match resp.read_to_string(&mut resp_str) {
+        Ok(_) => Ok(resp_str),
+        Err(e) => Err(io_error(
+            format!("Unable to read HTTP response of '{}' into string", url),
+            &e,
+        )),
+    }
+}
+
+pub(crate) fn read_file_into_string(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).chain_err(|| format!("Unable to open file path at {:?}", path))?;
+
+    let mut s = String::new();
+
+    file.read_to_string(&mut s)
+        .map(|_| s)
+        .chain_err(|| "Unable to read file into string")
+}
+
+pub(crate) fn read_file_into_bytes(path: &Path) -> Result<Vec<u8>> {
+    let mut file =
+        File::open(path).chain_err(|| format!("Unable to open file path at {:?}", path))?;
+
+    let mut bytes = Vec::new();
+
+    file.read_to_end(&mut bytes)
+        .map(|_| bytes)
+        .chain_err(|| format!("Unable to read file at {:?} into bytes", path))
+}
@@ -0,0 +1,598 @@
+use chrono::{DateTime, Duration, Utc};
+use config::HistoricalVersionsConfig;
+use errors::*;
+use glob::Pattern;
+use model::*;
+use regex::Regex;
+use rewrite::{UrlRewriter, UrlScope};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use version::VersionNumber;
+
+// const warning type names
+const CORE_WARNING_TYPE: &str = "core";
+const PLUGIN_WARNING_TYPE: &str = "plugin";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Vulnerability {
+    pub name: String,
+    pub version: String,
+    pub warning_id: String,
+    pub message: String,
+    pub url: String,
+    pub kept: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Incompatibility {
+    pub name: String,
+    pub version: String,
+    pub required_core: String,
+    pub kept: bool,
+}
+
+/// The checks of the published versions, applied to historical versions alike.
+pub(crate) struct VersionChecks<'a> {
+    pub target_core_version: Option<&'a str>,
+    pub keep_incompatible_plugins: bool,
+    pub check_security_warnings: bool,
+    pub keep_vulnerable_plugins: bool,
+}
+
+pub(crate) struct SelectedVersions {
+    pub plugin_versions: PluginVersions,
+    pub url_entries: Vec<UrlEntry>,
+    pub vulnerabilities: Vec<Vulnerability>,
+    pub incompatibilities: Vec<Incompatibility>,
+}
+
+// a warning along with its compiled version patterns
+struct WarningPattern {
+    warning: Warning,
+    patterns: Vec<Regex>,
+    // set when a pattern could not be compiled
+    matches_all: bool,
+}
+
+impl WarningPattern {
+    fn matches(&self, version: &str) -> bool {
+        self.matches_all || self.patterns.iter().any(|pattern| pattern.is_match(version))
+    }
+
+    fn vulnerability(&self, version: &str, kept: bool) -> Vulnerability {
+        warn!(
+            "'{}' {} is affected by {}: {}",
+            self.warning.name,
+            version,
+            self.warning.id,
+            self.warning.message
+        );
+
+        Vulnerability {
+            name: self.warning.name.to_owned(),
+            version: version.to_owned(),
+            warning_id: self.warning.id.to_owned(),
+            message: self.warning.message.to_owned(),
+            url: self.warning.url.to_owned(),
+            kept,
+        }
+    }
+}
+
+fn compile_warning_patterns(update_center: &UpdateCenter) -> Vec<WarningPattern> {
+    update_center
+        .warnings
+        .iter()
+        .flatten()
+        .map(|warning| {
+            let mut matches_all = false;
+            let mut patterns = Vec::new();
+
+            for warning_version in warning.versions.iter().flatten() {
+                // patterns are Java regexes that must match the whole version
+                match Regex::new(&format!("^(?:{})$", warning_version.pattern)) {
+                    Ok(pattern) => patterns.push(pattern),
+                    Err(e) => {
+                        // better to flag every version than to miss an affected one
+                        warn!(
+                            "Unable to parse version pattern '{}' of warning '{}', \
+                             treating every version of '{}' as affected: {}",
+                            warning_version.pattern, warning.id, warning.name, e
+                        );
+                        matches_all = true;
+                    }
+                }
+            }
+
+            WarningPattern {
+                warning: warning.clone(),
+                patterns,
+                matches_all,
+            }
+        })
+        .collect()
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .chain_err(|| format!("Unable to parse plugin name pattern '{}'", pattern))
+        })
+        .collect()
+}
+
+fn retain_plugins<F>(update_center: &mut UpdateCenter, is_kept: F) -> usize
+where
+    F: Fn(&str) -> bool,
+{
+    let plugin_count = update_center.plugins.len();
+    update_center.plugins.retain(|name, _| is_kept(name));
+    plugin_count - update_center.plugins.len()
+}
+
+pub(crate) fn filter_plugins(
+    update_center: &mut UpdateCenter,
+    plugin_includes: &[String],
+    plugin_excludes: &[String],
+) -> Result<()> {
+    let includes = compile_patterns(plugin_includes)?;
+    let excludes = compile_patterns(plugin_excludes)?;
+
+    // an empty include list means every plugin is included
+    let is_kept = |name: &str| {
+        (includes.is_empty() || includes.iter().any(|include| include.matches(name)))
+            && !excludes.iter().any(|exclude| exclude.matches(name))
+    };
+
+    let removed_count = retain_plugins(update_center, is_kept);
+
+    info!(
+        "Filtered out {} plugins, {} plugins remaining",
+        removed_count,
+        update_center.plugins.len()
+    );
+
+    Ok(())
+}
+
+fn resolve_plugin_closure(
+    update_center: &UpdateCenter,
+    plugin_roots: &[String],
+    include_optional_dependencies: bool,
+) -> Result<BTreeSet<String>> {
+    let mut closure = BTreeSet::new();
+    let mut pending: VecDeque<_> = plugin_roots.iter().cloned().collect();
+
+    for plugin_root in plugin_roots {
+        if !update_center.plugins.contains_key(plugin_root) {
+            bail!(format!(
+                "Unable to find root plugin '{}' under '{}'",
+                plugin_root,
+                PLUGINS_KEY
+            ));
+        }
+    }
+
+    while let Some(plugin_name) = pending.pop_front() {
+        if closure.contains(&plugin_name) {
+            continue;
+        }
+
+        let plugin = match update_center.plugins.get(&plugin_name) {
+            Some(plugin) => plugin,
+            None => {
+                warn!(
+                    "Unable to find dependency plugin '{}', leaving it out",
+                    plugin_name
+                );
+
+                continue;
+            }
+        };
+
+        for dependency in plugin.dependencies.iter().flatten() {
+            if !dependency.optional || include_optional_dependencies {
+                pending.push_back(dependency.name.to_owned());
+            }
+        }
+
+        closure.insert(plugin_name);
+    }
+
+    Ok(closure)
+}
+
+pub(crate) fn keep_plugin_closure(
+    update_center: &mut UpdateCenter,
+    plugin_roots: &[String],
+    include_optional_dependencies: bool,
+) -> Result<()> {
+    let closure =
+        resolve_plugin_closure(update_center, plugin_roots, include_optional_dependencies)?;
+
+    let removed_count = retain_plugins(update_center, |name| closure.contains(name));
+
+    info!(
+        "Resolved {} plugins from {} root plugins, {} plugins left out",
+        closure.len(),
+        plugin_roots.len(),
+        removed_count
+    );
+
+    Ok(())
+}
+
+pub(crate) fn filter_plugins_by_core(
+    update_center: &mut UpdateCenter,
+    target_core_version: &str,
+    keep_incompatible_plugins: bool,
+) -> Vec<Incompatibility> {
+    let target = VersionNumber::parse(target_core_version);
+
+    // plugins without 'requiredCore' are assumed to work on any core
+    let incompatibilities: Vec<_> = update_center
+        .plugins
+        .values()
+        .filter_map(|plugin| match plugin.required_core {
+            Some(ref required_core) if VersionNumber::parse(required_core) > target => {
+                Some(Incompatibility {
+                    name: plugin.name.to_owned(),
+                    version: plugin.version.to_owned(),
+                    required_core: required_core.to_owned(),
+                    kept: keep_incompatible_plugins,
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    let incompatible_names: BTreeSet<_> = incompatibilities
+        .iter()
+        .map(|incompatibility| incompatibility.name.as_str())
+        .collect();
+
+    if keep_incompatible_plugins {
+        for incompatible_name in &incompatible_names {
+            warn!(
+                "Plugin '{}' requires a core newer than {}",
+                incompatible_name,
+                target_core_version
+            );
+        }
+    } else {
+        retain_plugins(update_center, |name| !incompatible_names.contains(name));
+
+        info!(
+            "Filtered out {} plugins requiring a core newer than {}",
+            incompatible_names.len(),
+            target_core_version
+        );
+    }
+
+    incompatibilities
+}
+
+/// The URL of `target_core_version` in the layout of the upstream `core`,
+/// or `None` if the upstream core is not newer and needs no pinning.
+pub(crate) fn pinned_core_url(core: &Core, target_core_version: &str) -> Result<Option<String>> {
+    // an older upstream core is already compatible with the target
+    if VersionNumber::parse(&core.version) <= VersionNumber::parse(target_core_version) {
+        return Ok(None);
+    }
+
+    // download URLs are laid out as '.../war/<version>/jenkins.war'
+    let version_segment = format!("/{}/", core.version);
+
+    if !core.url.contains(&version_segment) {
+        bail!(format!(
+            "Unable to find version '{}' in core URL '{}' to pin it to {}",
+            core.version,
+            core.url,
+            target_core_version
+        ));
+    }
+
+    Ok(Some(core.url.replace(
+        &version_segment,
+        &format!("/{}/", target_core_version),
+    )))
+}
+
+pub(crate) fn pin_core(update_center: &mut UpdateCenter, pinned_core: &Core) {
+    info!(
+        "Pinning core from {} to {} at '{}'",
+        update_center.core.version,
+        pinned_core.version,
+        pinned_core.url
+    );
+
+    update_center.core = pinned_core.clone();
+}
+
+pub(crate) fn find_vulnerabilities(
+    update_center: &mut UpdateCenter,
+    keep_vulnerable_plugins: bool,
+) -> Result<Vec<Vulnerability>> {
+    let mut vulnerabilities = Vec::new();
+
+    for warning_pattern in &compile_warning_patterns(update_center) {
+        let warning = &warning_pattern.warning;
+
+        let version = if warning.warning_type == CORE_WARNING_TYPE {
+            Some(&update_center.core.version)
+        } else if warning.warning_type == PLUGIN_WARNING_TYPE {
+            update_center
+                .plugins
+                .get(&warning.name)
+                .map(|plugin| &plugin.version)
+        } else {
+            None
+        };
+
+        let version = match version {
+            Some(version) => version,
+            None => continue,
+        };
+
+        if warning_pattern.matches(version) {
+            // the core itself can only ever be flagged
+            let kept = keep_vulnerable_plugins || warning.warning_type == CORE_WARNING_TYPE;
+            vulnerabilities.push(warning_pattern.vulnerability(version, kept));
+        }
+    }
+
+    let removed_count = retain_plugins(update_center, |name| {
+        !vulnerabilities
+            .iter()
+            .any(|vulnerability| !vulnerability.kept && vulnerability.name == name)
+    });
+
+    info!(
+        "Found {} security warnings affecting published versions, {} plugins filtered out",
+        vulnerabilities.len(),
+        removed_count
+    );
+
+    Ok(vulnerabilities)
+}
+
+/// Selects the historical versions to mirror, leaving out those that the
+/// published versions would be filtered out for by `checks`.
+pub(crate) fn select_plugin_versions(
+    update_center: &UpdateCenter,
+    plugin_versions: &PluginVersions,
+    historical_versions: &HistoricalVersionsConfig,
+    checks: &VersionChecks,
+    rewriter: &UrlRewriter,
+) -> Result<SelectedVersions> {
+    let warning_patterns = if checks.check_security_warnings {
+        compile_warning_patterns(update_center)
+    } else {
+        Vec::new()
+    };
+
+    let target = checks.target_core_version.map(VersionNumber::parse);
+    let mut vulnerabilities = Vec::new();
+    let mut incompatibilities = Vec::new();
+
+    let min_release_time = historical_versions
+        .max_age_days
+        .map(|max_age_days| Utc::now() - Duration::days(i64::from(max_age_days)));
+
+    let mut selected_plugins = BTreeMap::new();
+    let mut url_entries = Vec::new();
+
+    // only plugins still published in the update-center are of interest
+    for (plugin_name, plugin) in &update_center.plugins {
+        let versions = match plugin_versions.plugins.get(plugin_name) {
+            Some(versions) => versions,
+            None => continue,
+        };
+
+        let mut versions: Vec<_> = versions
+            .iter()
+            .filter(|(_, version_plugin)| match min_release_time {
+                Some(min_release_time) => version_plugin
+                    .extra
+                    .get(RELEASE_TIMESTAMP_KEY)
+                    .and_then(Value::as_str)
+                    .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+                    .map(|release_time| release_time >= min_release_time)
+                    .unwrap_or(false),
+                None => true,
+            })
+            .collect();
+
+        // newest versions first
+        versions.sort_by(|(lhs, _), (rhs, _)| {
+            VersionNumber::parse(rhs).cmp(&VersionNumber::parse(lhs))
+        });
+
+        let mut selected_versions = BTreeMap::new();
+
+        for (version, version_plugin) in versions {
+            if Some(selected_versions.len()) == historical_versions.version_count {
+                break;
+            }
+
+            // the current version has been checked along with the update-center
+            if *version != plugin.version {
+                let warning_pattern = warning_patterns.iter().find(|warning_pattern| {
+                    warning_pattern.warning.warning_type == PLUGIN_WARNING_TYPE
+                        && warning_pattern.warning.name == *plugin_name
+                        && warning_pattern.matches(version)
+                });
+
+                if let Some(warning_pattern) = warning_pattern {
+                    let kept = checks.keep_vulnerable_plugins;
+                    vulnerabilities.push(warning_pattern.vulnerability(version, kept));
+
+                    if !kept {
+                        continue;
+                    }
+                }
+
+                let required_core = match (&target, &version_plugin.required_core) {
+                    (Some(target), Some(required_core))
+                        if VersionNumber::parse(required_core) > *target =>
+                    {
+                        Some(required_core)
+                    }
+                    _ => None,
+                };
+
+                if let Some(required_core) = required_core {
+                    incompatibilities.push(Incompatibility {
+                        name: plugin_name.to_owned(),
+                        version: version.to_owned(),
+                        required_core: required_core.to_owned(),
+                        kept: checks.keep_incompatible_plugins,
+                    });
+
+                    if !checks.keep_incompatible_plugins {
+                        continue;
+                    }
+                }
+            }
+
+            let version_path = format!("{}.{}.{}", PLUGINS_KEY, plugin_name, version);
+            let mut version_plugin = version_plugin.clone();
+
+            // the current version is already part of the URL list
+            if *version != plugin.version {
+                url_entries.push(version_plugin.url_entry());
+            }
+
+            rewriter.rewrite_field(
+                &mut version_plugin.url,
+                &version_path,
+                URL_KEY,
+                UrlScope::Plugins,
+            );
+
+            selected_versions.insert(version.to_owned(), version_plugin);
+        }
+
+        selected_plugins.insert(plugin_name.to_owned(), selected_versions);
+    }
+
+    info!(
+        "Selected {} historical plugin versions for mirroring",
+        url_entries.len()
+    );
+
+    Ok(SelectedVersions {
+        plugin_versions: PluginVersions {
+            plugins: selected_plugins,
+            extra: plugin_versions.extra.clone(),
+        },
+        url_entries,
+        vulnerabilities,
+        incompatibilities,
+    })
+}
+
+pub(crate) fn check_plugin_dependencies(update_center: &UpdateCenter) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (plugin_name, plugin) in &update_center.plugins {
+        for dependency in plugin.dependencies.iter().flatten() {
+            match update_center.plugins.get(&dependency.name) {
+                // optional dependencies only matter if they are present
+                None if dependency.optional => (),
+                None => problems.push(format!(
+                    "Plugin '{}' requires '{}' >= {}, but it is missing",
+                    plugin_name,
+                    dependency.name,
+                    dependency.version
+                )),
+                Some(kept) => {
+                    if VersionNumber::parse(&kept.version)
+                        < VersionNumber::parse(&dependency.version)
+                    {
+                        problems.push(format!(
+                            "Plugin '{}' requires '{}' >= {}, but only {} is kept",
+                            plugin_name,
+                            dependency.name,
+                            dependency.version,
+                            kept.version
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn update_center(plugins: Value) -> UpdateCenter {
+        serde_json::from_value(json!({
+            "connectionCheckUrl": "http://www.google.com/",
+            "core": {"url": "http://mirror/jenkins.war", "version": "2.361"},
+            "plugins": plugins
+        }))
+        .unwrap()
+    }
+
+    fn plugin(name: &str, version: &str, dependencies: Value) -> Value {
+        json!({
+            "name": name,
+            "version": version,
+            "url": format!("http://mirror/{}.hpi", name),
+            "dependencies": dependencies
+        })
+    }
+
+    #[test]
+    fn accepts_satisfied_dependencies() {
+        let update_center = update_center(json!({
+            "a": plugin("a", "1.0", json!([{"name": "b", "optional": false, "version": "2.0"}])),
+            "b": plugin("b", "2.0.1", json!([]))
+        }));
+
+        assert!(check_plugin_dependencies(&update_center).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_required_dependencies() {
+        let update_center = update_center(json!({
+            "a": plugin("a", "1.0", json!([{"name": "b", "optional": false, "version": "2.0"}]))
+        }));
+
+        assert_eq!(
+            check_plugin_dependencies(&update_center),
+            vec!["Plugin 'a' requires 'b' >= 2.0, but it is missing"]
+        );
+    }
+
+    #[test]
+    fn ignores_missing_optional_dependencies() {
+        let update_center = update_center(json!({
+            "a": plugin("a", "1.0", json!([{"name": "b", "optional": true, "version": "2.0"}]))
+        }));
+
+        assert!(check_plugin_dependencies(&update_center).is_empty());
+    }
+
+    #[test]
+    fn reports_too_old_dependencies() {
+        // optional dependencies that are kept must still be recent enough
+        let update_center = update_center(json!({
+            "a": plugin("a", "1.0", json!([{"name": "b", "optional": true, "version": "2.10"}])),
+            "b": plugin("b", "2.9", json!([]))
+        }));
+
+        assert_eq!(
+            check_plugin_dependencies(&update_center),
+            vec!["Plugin 'a' requires 'b' >= 2.10, but only 2.9 is kept"]
+        );
+    }
+}
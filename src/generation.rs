@@ -0,0 +1,327 @@
+//! Blue/green layout of the sync root, where every sync fills a fresh
+//! directory under `generations/` and the `current` symlink is switched over
+//! to it only once complete. Rolling back is a matter of pointing `current`
+//! at an older generation again.
+
+use chrono::Utc;
+use errors::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const GENERATIONS_DIR_NAME: &str = "generations";
+const CURRENT_LINK_NAME: &str = "current";
+
+// partial downloads, kept outside of the generations so that the next sync
+// can resume those of a discarded one
+const PARTIAL_DIR_NAME: &str = ".partial";
+
+// sorts in creation order
+const GENERATION_NAME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A generation about to be filled by a sync.
+pub struct Generation {
+    sync_root_dir_path: PathBuf,
+    name: String,
+    /// Where the artifacts of the new generation go.
+    pub dir_path: String,
+    /// The generation `current` points at, if any.
+    pub current_dir_path: Option<PathBuf>,
+}
+
+impl Generation {
+    pub fn new(sync_root_dir_path: &str) -> Generation {
+        let sync_root_dir_path = PathBuf::from(sync_root_dir_path);
+        let generations_dir_path = sync_root_dir_path.join(GENERATIONS_DIR_NAME);
+        let timestamp = Utc::now().format(GENERATION_NAME_FORMAT).to_string();
+
+        // syncs within the same second get a suffix, which still sorts after
+        // the unsuffixed name and before the next second
+        let mut name = timestamp.clone();
+        let mut suffix = 0;
+
+        while generations_dir_path.join(&name).exists() {
+            suffix += 1;
+            name = format!("{}-{:02}", timestamp, suffix);
+        }
+
+        let dir_path = generations_dir_path
+            .join(&name)
+            .to_string_lossy()
+            .into_owned();
+
+        // the link target is relative to the sync root
+        let current_dir_path = fs::read_link(sync_root_dir_path.join(CURRENT_LINK_NAME))
+            .ok()
+            .map(|target| sync_root_dir_path.join(target));
+
+        Generation {
+            sync_root_dir_path,
+            name,
+            dir_path,
+            current_dir_path,
+        }
+    }
+
+    /// Creates the generation directory, hardlinking every download path
+    /// already present in the current generation, so that only new or
+    /// changed artifacts need downloading.
+    pub fn prepare(&self, download_paths: &[&str]) -> Result<()> {
+        let generations_dir_path = self.sync_root_dir_path.join(GENERATIONS_DIR_NAME);
+
+        fs::create_dir_all(&generations_dir_path).chain_err(|| {
+            format!("Unable to create directory chain {:?}", generations_dir_path)
+        })?;
+
+        fs::create_dir(&self.dir_path)
+            .chain_err(|| format!("Unable to create generation directory '{}'", self.dir_path))?;
+
+        let current_dir_path = match self.current_dir_path {
+            Some(ref current_dir_path) => current_dir_path,
+            None => {
+                info!("No current generation to link from, downloading everything");
+                return Ok(());
+            }
+        };
+
+        let mut link_count = 0;
+
+        for download_path in download_paths {
+            let rel_path = match Path::new(download_path).strip_prefix(&self.dir_path) {
+                Ok(rel_path) => rel_path,
+                Err(_) => continue,
+            };
+
+            let current_path = current_dir_path.join(rel_path);
+
+            if !current_path.is_file() {
+                continue;
+            }
+
+            if let Some(parent_dir_path) = Path::new(download_path).parent() {
+                fs::create_dir_all(parent_dir_path).chain_err(|| {
+                    format!("Unable to create directory chain {:?}", parent_dir_path)
+                })?;
+            }
+
+            fs::hard_link(&current_path, download_path).chain_err(|| {
+                format!("Unable to link {:?} into '{}'", current_path, download_path)
+            })?;
+
+            link_count += 1;
+        }
+
+        info!(
+            "Linked {} artifacts from current generation {:?}",
+            link_count,
+            current_dir_path
+        );
+
+        Ok(())
+    }
+
+    /// Where the partial download of `download_path` goes, mirrored under
+    /// the `.partial` directory of the sync root.
+    pub fn partial_path(&self, download_path: &str) -> String {
+        match Path::new(download_path).strip_prefix(&self.dir_path) {
+            Ok(rel_path) => self
+                .sync_root_dir_path
+                .join(PARTIAL_DIR_NAME)
+                .join(rel_path)
+                .to_string_lossy()
+                .into_owned(),
+            Err(_) => download_path.to_owned(),
+        }
+    }
+
+    /// Atomically points `current` at this generation, then removes all but
+    /// the last `keep_count` generations, along with partial downloads of
+    /// artifacts no longer listed.
+    pub fn switch(&self, keep_count: usize) -> Result<()> {
+        let link_path = self.sync_root_dir_path.join(CURRENT_LINK_NAME);
+
+        let temp_link_path = self
+            .sync_root_dir_path
+            .join(format!(".{}.{}.tmp", CURRENT_LINK_NAME, process::id()));
+
+        let target = Path::new(GENERATIONS_DIR_NAME).join(&self.name);
+
+        // a stale link from an interrupted switch would fail the creation
+        let _ = fs::remove_file(&temp_link_path);
+
+        symlink_dir(&target, &temp_link_path)
+            .chain_err(|| format!("Unable to create symlink {:?}", temp_link_path))?;
+
+        fs::rename(&temp_link_path, &link_path).chain_err(|| {
+            let _ = fs::remove_file(&temp_link_path);
+            format!("Unable to switch {:?} over to {:?}", link_path, target)
+        })?;
+
+        info!("Switched {:?} over to {:?}", link_path, target);
+
+        // every listed artifact has completed, so whatever is left is stale
+        let partial_dir_path = self.sync_root_dir_path.join(PARTIAL_DIR_NAME);
+
+        if partial_dir_path.exists() {
+            if let Err(e) = fs::remove_dir_all(&partial_dir_path) {
+                error!("Unable to remove stale partial downloads {:?}: {}", partial_dir_path, e);
+            }
+        }
+
+        self.prune(keep_count.max(1))
+    }
+
+    /// Removes the generation after a failed sync, leaving `current` as is.
+    /// Partial downloads are kept for the next sync to resume.
+    pub fn discard(self) {
+        match fs::remove_dir_all(&self.dir_path) {
+            Ok(_) => info!("Discarded incomplete generation '{}'", self.dir_path),
+            Err(e) => error!("Unable to discard generation '{}': {}", self.dir_path, e),
+        }
+    }
+
+    fn prune(&self, keep_count: usize) -> Result<()> {
+        let generations_dir_path = self.sync_root_dir_path.join(GENERATIONS_DIR_NAME);
+
+        let entries = fs::read_dir(&generations_dir_path).chain_err(|| {
+            format!("Unable to list generations in {:?}", generations_dir_path)
+        })?;
+
+        let mut names: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+
+        let prune_count = names.len().saturating_sub(keep_count);
+
+        for name in names.iter().take(prune_count).filter(|name| **name != self.name) {
+            let dir_path = generations_dir_path.join(name);
+
+            match fs::remove_dir_all(&dir_path) {
+                Ok(_) => info!("Removed old generation {:?}", dir_path),
+                Err(e) => error!("Unable to remove old generation {:?}: {}", dir_path, e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn symlink_dir(target: &Path, link_path: &Path) -> ::std::io::Result<()> {
+    ::std::os::unix::fs::symlink(target, link_path)
+}
+
+#[cfg(windows)]
+fn symlink_dir(target: &Path, link_path: &Path) -> ::std::io::Result<()> {
+    ::std::os::windows::fs::symlink_dir(target, link_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // a fresh sync root of its own
+    fn sync_root(name: &str) -> String {
+        let dir_path = env::temp_dir().join(format!(
+            "jenkins-update-mod-generation-{}-{}",
+            name,
+            process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir_path);
+        fs::create_dir_all(&dir_path).unwrap();
+        dir_path.to_string_lossy().into_owned()
+    }
+
+    fn generation_names(sync_root: &str) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(Path::new(sync_root).join(GENERATIONS_DIR_NAME))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn names_generations_in_creation_order() {
+        let sync_root = sync_root("names");
+        let mut names = Vec::new();
+
+        // quick enough to land within the same second
+        for _ in 0..3 {
+            let generation = Generation::new(&sync_root);
+            generation.prepare(&[]).unwrap();
+            names.push(generation.name);
+        }
+
+        assert_eq!(generation_names(&sync_root), names);
+    }
+
+    #[test]
+    fn links_current_artifacts_into_new_generations() {
+        let sync_root = sync_root("link");
+
+        let first = Generation::new(&sync_root);
+        first.prepare(&[]).unwrap();
+        fs::create_dir(Path::new(&first.dir_path).join("plugins")).unwrap();
+        fs::write(Path::new(&first.dir_path).join("plugins/a.hpi"), b"a").unwrap();
+        first.switch(2).unwrap();
+
+        let second = Generation::new(&sync_root);
+        assert_eq!(second.current_dir_path, Some(PathBuf::from(&first.dir_path)));
+
+        let a_path = format!("{}/plugins/a.hpi", second.dir_path);
+        let b_path = format!("{}/plugins/b.hpi", second.dir_path);
+        second.prepare(&[&a_path, &b_path]).unwrap();
+
+        assert_eq!(fs::read(&a_path).unwrap(), b"a");
+        assert!(!Path::new(&b_path).exists());
+        assert_eq!(
+            second.partial_path(&b_path),
+            format!("{}/{}/plugins/b.hpi", sync_root, PARTIAL_DIR_NAME)
+        );
+    }
+
+    #[test]
+    fn prunes_old_generations_on_switch() {
+        let sync_root = sync_root("prune");
+        let mut names = Vec::new();
+
+        for _ in 0..3 {
+            let generation = Generation::new(&sync_root);
+            generation.prepare(&[]).unwrap();
+            // the generation directory itself maps onto the partial directory
+            fs::create_dir_all(generation.partial_path(&generation.dir_path)).unwrap();
+            generation.switch(2).unwrap();
+            names.push(generation.name);
+        }
+
+        assert_eq!(generation_names(&sync_root), &names[1..]);
+        assert_eq!(
+            fs::read_link(Path::new(&sync_root).join(CURRENT_LINK_NAME)).unwrap(),
+            Path::new(GENERATIONS_DIR_NAME).join(&names[2])
+        );
+        assert!(!Path::new(&sync_root).join(PARTIAL_DIR_NAME).exists());
+    }
+
+    #[test]
+    fn discards_failed_generations() {
+        let sync_root = sync_root("discard");
+
+        let first = Generation::new(&sync_root);
+        first.prepare(&[]).unwrap();
+        first.switch(2).unwrap();
+
+        let second = Generation::new(&sync_root);
+        second.prepare(&[]).unwrap();
+        second.discard();
+
+        assert_eq!(generation_names(&sync_root), vec![first.name]);
+    }
+}
@@ -0,0 +1,125 @@
+extern crate jenkins_update_mod;
+extern crate log4rs;
+#[macro_use]
+extern crate log;
+extern crate structopt;
+
+use jenkins_update_mod::config::{self, CombinedConfig};
+use jenkins_update_mod::errors::*;
+use jenkins_update_mod::model::dedup_url_entries;
+use jenkins_update_mod::publish::has_staged;
+use jenkins_update_mod::{modify, sync};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "jenkins-update-mod", about = "Jenkins update-center mirroring")]
+struct ArgConfig {
+    #[structopt(short = "c", long = "config", help = "Combined file configuration path")]
+    config_path: String,
+
+    #[structopt(short = "l", long = "log-config", help = "Log configuration file path")]
+    log_config_path: String,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    #[structopt(name = "mod", about = "Modify the update-center JSON and write its URL list")]
+    Mod,
+
+    #[structopt(name = "sync", about = "Sync the artifacts in the URL list")]
+    Sync,
+
+    #[structopt(name = "all", about = "Modify, then sync all the resulting URL lists")]
+    All,
+}
+
+fn run() -> Result<()> {
+    let arg_config = ArgConfig::from_args();
+
+    log4rs::init_file(&arg_config.log_config_path, Default::default()).chain_err(|| {
+        format!(
+            "Unable to initialize log4rs logger with the given config file at '{}'",
+            arg_config.log_config_path
+        )
+    })?;
+
+    let mut config: CombinedConfig = config::read_config(Path::new(&arg_config.config_path))?;
+
+    // the sync step promotes whatever the mod step has staged
+    if config.sync.staging_dir_path.is_none() {
+        config.sync.staging_dir_path = config.mod_config.staging_dir_path.clone();
+    }
+
+    if config.sync.retry.is_none() {
+        config.sync.retry = config.mod_config.retry.clone();
+    }
+
+    info!("Completed configuration initialization!");
+
+    match arg_config.command {
+        Command::Mod => modify::run_mod(&config.mod_config)?.save_state(&config.mod_config),
+
+        Command::Sync => {
+            let url_entries = sync::read_url_list(config.url_list_json_file_path())?;
+            sync::execute_sync(&config.sync, sync::plan_sync(&config.sync, url_entries))
+        }
+
+        Command::All => {
+            let mod_run = modify::run_mod(&config.mod_config)?;
+
+            let is_staged = match config.sync.staging_dir_path {
+                Some(ref staging_dir_path) => has_staged(staging_dir_path),
+                None => false,
+            };
+
+            let url_entries = match mod_run.url_lists {
+                // every core variant and site is synced into the same root
+                Some(ref url_lists) => {
+                    dedup_url_entries(url_lists.values().flatten().cloned().collect())
+                }
+                None if is_staged => {
+                    info!("Syncing the outputs of the last run, which are still staged");
+                    sync::read_url_list(config.url_list_json_file_path())?
+                }
+                None => {
+                    info!("Skipping sync as nothing has changed upstream");
+                    return mod_run.save_state(&config.mod_config);
+                }
+            };
+
+            sync::execute_sync(&config.sync, sync::plan_sync(&config.sync, url_entries))?;
+
+            // a failed sync leaves the state as is, for the next run to retry
+            mod_run.save_state(&config.mod_config)
+        }
+    }
+}
+
+fn main() {
+    match run() {
+        Ok(_) => {
+            // stdout may be carrying the modified JSON
+            writeln!(io::stderr(), "Program completed!").expect("Unable to write into stderr!");
+            process::exit(0)
+        }
+
+        Err(ref e) => {
+            let stderr = &mut io::stderr();
+
+            writeln!(stderr, "Error: {}", e).expect("Unable to write error into stderr!");
+
+            for e in e.iter().skip(1) {
+                writeln!(stderr, "- Caused by: {}", e)
+                    .expect("Unable to write error causes into stderr!");
+            }
+
+            process::exit(1);
+        }
+    }
+}
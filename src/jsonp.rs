@@ -0,0 +1,71 @@
+use errors::*;
+use regex::Regex;
+use serde_json::{self, Value};
+
+// a JavaScript callback such as 'updateCenter.post' with its '(', and the closing ')',
+// each taking the whitespace next to the JSON along
+const JSONP_PREFIX_PATTERN: &str = r"^\s*[A-Za-z_$][\w$]*(?:\s*\.\s*[A-Za-z_$][\w$]*)*\s*\(\s*";
+const JSONP_SUFFIX_PATTERN: &str = r"\s*\)\s*;?\s*$";
+
+/// Text found around the JSON, kept so that the same wrapper style can be
+/// written back out. Both are only whitespace for plain JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonpEnvelope {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl JsonpEnvelope {
+    pub fn wrap(&self, json_str: &str) -> String {
+        format!("{}{}{}", self.prefix, json_str, self.suffix)
+    }
+}
+
+/// Parses either plain JSON or JSON padded with a callback, such as
+/// `updateCenter.post(...);`, detecting the envelope around it.
+pub fn parse_jsonp(resp_str: &str) -> Result<(Value, JsonpEnvelope)> {
+    let trimmed_resp_str = resp_str.trim_start();
+
+    let (json_start, json_end) = if trimmed_resp_str.starts_with('{')
+        || trimmed_resp_str.starts_with('[')
+    {
+        (
+            resp_str.len() - trimmed_resp_str.len(),
+            resp_str.trim_end().len(),
+        )
+    } else {
+        let prefix_regex =
+            Regex::new(JSONP_PREFIX_PATTERN).chain_err(|| "Unable to parse JSONP prefix regex")?;
+
+        let suffix_regex =
+            Regex::new(JSONP_SUFFIX_PATTERN).chain_err(|| "Unable to parse JSONP suffix regex")?;
+
+        let json_start = match prefix_regex.find(resp_str) {
+            Some(prefix_match) => prefix_match.end(),
+            None => bail!(format!(
+                "Unable to detect JSON or JSONP envelope in content starting with: {:?}",
+                resp_str.chars().take(40).collect::<String>()
+            )),
+        };
+
+        let json_end = match suffix_regex.find(&resp_str[json_start..]) {
+            Some(suffix_match) => json_start + suffix_match.start(),
+            None => bail!(format!(
+                "Unable to find the closing ')' of JSONP callback '{}'",
+                resp_str[..json_start].trim()
+            )),
+        };
+
+        (json_start, json_end)
+    };
+
+    let resp_json = serde_json::from_str(&resp_str[json_start..json_end])
+        .chain_err(|| "Unable to parse JSON within JSONP envelope into JSON value.")?;
+
+    let envelope = JsonpEnvelope {
+        prefix: resp_str[..json_start].to_owned(),
+        suffix: resp_str[json_end..].to_owned(),
+    };
+
+    Ok((resp_json, envelope))
+}
@@ -0,0 +1,57 @@
+//! Mirroring of a Jenkins update-center, shared by the `jenkins-mod` and
+//! `jenkins-sync` binaries.
+//!
+//! `modify` fetches and rewrites update-center.json for a mirror, while
+//! `sync` downloads every artifact listed in the URL list it produces.
+
+#[macro_use]
+extern crate error_chain;
+extern crate chrono;
+extern crate flate2;
+extern crate futures;
+extern crate futures_cpupool;
+extern crate glob;
+extern crate hyper;
+#[macro_use]
+extern crate log;
+extern crate openssl;
+extern crate regex;
+extern crate serde;
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+extern crate url;
+extern crate walkdir;
+
+pub mod errors {
+    error_chain! {
+        errors {
+            // worth retrying, after the delay asked for by the server if any
+            Transient(msg: String, retry_after: Option<::std::time::Duration>) {
+                description("transient failure")
+                display("{}", msg)
+            }
+        }
+    }
+}
+
+pub mod config;
+pub mod fetch;
+mod filter;
+pub mod generation;
+pub mod jsonp;
+pub mod model;
+pub mod modify;
+pub mod publish;
+mod retry;
+mod rewrite;
+pub mod signature;
+pub mod state;
+pub mod sync;
+mod tls;
+pub mod version;
+
+pub use filter::{Incompatibility, Vulnerability};
+pub use rewrite::UrlChange;
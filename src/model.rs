@@ -0,0 +1,198 @@
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+
+// const key names, used for reporting field paths
+pub(crate) const CORE_KEY: &str = "core";
+pub(crate) const DEPRECATIONS_KEY: &str = "deprecations";
+pub(crate) const GENERATION_TIMESTAMP_KEY: &str = "generationTimestamp";
+pub(crate) const ISSUE_TRACKERS_KEY: &str = "issueTrackers";
+pub(crate) const PLUGINS_KEY: &str = "plugins";
+pub(crate) const REPORT_URL_KEY: &str = "reportUrl";
+pub(crate) const SCM_KEY: &str = "scm";
+pub(crate) const SIGNATURE_KEY: &str = "signature";
+pub(crate) const URL_KEY: &str = "url";
+pub(crate) const VIEW_URL_KEY: &str = "viewUrl";
+pub(crate) const WARNINGS_KEY: &str = "warnings";
+pub(crate) const WIKI_KEY: &str = "wiki";
+
+// const signature key names, used for reporting verification failures
+pub(crate) const CORRECT_DIGEST_KEY: &str = "correct_digest";
+pub(crate) const CORRECT_DIGEST512_KEY: &str = "correct_digest512";
+pub(crate) const CORRECT_SIGNATURE_KEY: &str = "correct_signature";
+pub(crate) const CORRECT_SIGNATURE512_KEY: &str = "correct_signature512";
+
+// key of the plugin-versions.json entries not modelled by `Plugin`
+pub(crate) const RELEASE_TIMESTAMP_KEY: &str = "releaseTimestamp";
+
+// key of the core entry not modelled by `Core`
+pub(crate) const SIZE_KEY: &str = "size";
+
+pub type MapStrVal = Map<String, Value>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UrlEntry {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Typed update-center.json, where every field not modelled here is kept in
+/// `extra` so that nothing is lost on the way back out.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCenter {
+    pub connection_check_url: String,
+    pub core: Core,
+    pub plugins: BTreeMap<String, Plugin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<Warning>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecations: Option<BTreeMap<String, Deprecation>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Core {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub url: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Plugin {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_core: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<Vec<Dependency>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wiki: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue_trackers: Option<Vec<IssueTracker>>,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub optional: bool,
+    pub version: String,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueTracker {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_url: Option<String>,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Warning {
+    pub id: String,
+    pub message: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub warning_type: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<WarningVersion>>,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WarningVersion {
+    pub pattern: String,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Deprecation {
+    pub url: String,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Signature {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificates: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct_digest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct_digest512: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct_signature512: Option<String>,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+/// Typed plugin-versions.json, keyed by plugin name and then by version.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PluginVersions {
+    pub plugins: BTreeMap<String, BTreeMap<String, Plugin>>,
+    #[serde(flatten)]
+    pub extra: MapStrVal,
+}
+
+impl Core {
+    pub fn url_entry(&self) -> UrlEntry {
+        UrlEntry {
+            url: self.url.to_owned(),
+            sha1: self.sha1.clone(),
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+impl Plugin {
+    pub fn url_entry(&self) -> UrlEntry {
+        UrlEntry {
+            url: self.url.to_owned(),
+            sha1: self.sha1.clone(),
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+/// Keeps only the first entry of every URL, preserving the order.
+pub fn dedup_url_entries(url_entries: Vec<UrlEntry>) -> Vec<UrlEntry> {
+    let mut seen_urls = HashSet::new();
+
+    url_entries
+        .into_iter()
+        .filter(|url_entry| seen_urls.insert(url_entry.url.to_owned()))
+        .collect()
+}
@@ -0,0 +1,774 @@
+use config::*;
+use errors::*;
+use fetch::{fetch_string, fetch_string_if_modified, new_client, ConditionalFetch, STDIO_PATH};
+use filter::*;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hyper::client::Client;
+use jsonp::{parse_jsonp, JsonpEnvelope};
+use model::*;
+use openssl::base64;
+use publish::PendingOutputs;
+use retry::with_retries;
+use rewrite::*;
+use serde_json;
+use signature::{from_hex, sign_update_center, verify_update_center};
+use state::{config_digest, ModState, SiteState};
+use version::VersionNumber;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// file names of the extra outputs written next to the modified JSON
+const PLUGIN_VERSIONS_FILE_NAME: &str = "plugin-versions.json";
+const SECURITY_REPORT_FILE_NAME: &str = "security-report.json";
+const COMPATIBILITY_REPORT_FILE_NAME: &str = "compatibility-report.json";
+const URL_REWRITE_REPORT_FILE_NAME: &str = "url-rewrite-report.json";
+
+// 'update-center.actual.json' holds the plain JSON without the JSONP envelope
+const ACTUAL_JSON_INFIX: &str = "actual";
+const GZIP_EXT: &str = ".gz";
+
+// checksum published next to each 'jenkins.war'
+const SHA256_EXT: &str = ".sha256";
+
+// placeholder in 'stable_update_center_url'
+const LINE_PLACEHOLDER: &str = "{line}";
+
+/// A core version to filter plugins by, along with the release to pin the
+/// core to if upstream is newer.
+pub struct TargetCore {
+    pub version: String,
+    pub pinned_core: Option<Core>,
+}
+
+pub struct VariantOutput {
+    pub urls: Vec<UrlEntry>,
+    pub vulnerabilities: Vec<Vulnerability>,
+    pub incompatibilities: Vec<Incompatibility>,
+    pub plugin_versions: Option<PluginVersions>,
+    pub url_changes: Vec<UrlChange>,
+}
+
+/// Fetches the update-center of `site`, verifying its signature first if
+/// `verification` is given. Also returns the JSONP envelope to write it
+/// back out with.
+pub fn fetch_update_center(
+    client: &Client,
+    site: &SiteConfig,
+    verification: Option<&VerificationConfig>,
+) -> Result<(UpdateCenter, JsonpEnvelope)> {
+    let resp_str = fetch_string(client, &site.update_center_url)?;
+    parse_update_center(&resp_str, site, verification)
+}
+
+pub fn parse_update_center(
+    resp_str: &str,
+    site: &SiteConfig,
+    verification: Option<&VerificationConfig>,
+) -> Result<(UpdateCenter, JsonpEnvelope)> {
+    let (resp_json, mut envelope) = parse_jsonp(resp_str)?;
+
+    if let Some(ref suppress_front) = site.suppress_front {
+        envelope.prefix = suppress_front.to_owned();
+    }
+
+    if let Some(ref suppress_back) = site.suppress_back {
+        envelope.suffix = suppress_back.to_owned();
+    }
+
+    // refuse to touch anything that does not come from a trusted upstream
+    if let Some(verification) = verification {
+        verify_update_center(&resp_json, verification)?;
+        info!("Verified upstream update-center signature");
+    }
+
+    let update_center = serde_json::from_value(resp_json)
+        .chain_err(|| "Unable to parse update-center JSON value into typed form.")?;
+
+    Ok((update_center, envelope))
+}
+
+pub fn fetch_plugin_versions(
+    client: &Client,
+    historical_versions: &HistoricalVersionsConfig,
+) -> Result<PluginVersions> {
+    let plugin_versions_str = fetch_string(client, &historical_versions.plugin_versions_url)?;
+
+    serde_json::from_str(&plugin_versions_str)
+        .chain_err(|| "Unable to parse plugin versions JSON string into typed form.")
+}
+
+/// Fetches the core of the LTS update-center of `line`, such as `2.401`.
+pub fn fetch_stable_core(client: &Client, config: &ModConfig, line: &str) -> Result<Core> {
+    let stable_update_center_url = match config.stable_update_center_url {
+        Some(ref stable_update_center_url) => {
+            stable_update_center_url.replace(LINE_PLACEHOLDER, line)
+        }
+        None => bail!(format!(
+            "Expected 'stable_update_center_url' to be present to resolve core line {}",
+            line
+        )),
+    };
+
+    let resp_str = with_retries(config.retry.as_ref(), || {
+        fetch_string(client, &stable_update_center_url)
+    })?;
+
+    let (mut resp_json, _) = parse_jsonp(&resp_str)?;
+
+    if let Some(ref verification) = config.verification {
+        verify_update_center(&resp_json, verification)?;
+    }
+
+    let stable_core = match resp_json.get_mut(CORE_KEY) {
+        Some(core) => serde_json::from_value::<Core>(core.take()).chain_err(|| {
+            format!("Unable to parse core of '{}'", stable_update_center_url)
+        })?,
+        None => bail!(format!(
+            "Unable to find '{}' in '{}'",
+            CORE_KEY,
+            stable_update_center_url
+        )),
+    };
+
+    info!("Resolved core line {} into release {}", line, stable_core.version);
+    Ok(stable_core)
+}
+
+/// Resolves `core_target` against the upstream `core`. A core line stands
+/// for the release of its LTS update-center, with checksums included. The
+/// checksum of a pinned release is fetched from next to its 'jenkins.war'
+/// instead, since the upstream ones belong to the newer core.
+pub fn resolve_target_core(
+    client: &Client,
+    config: &ModConfig,
+    stable_cores: &mut BTreeMap<String, Core>,
+    core: &Core,
+    core_target: CoreTarget,
+) -> Result<TargetCore> {
+    let target_core_version = match core_target {
+        CoreTarget::Version(version) => version,
+        CoreTarget::Line(line) => {
+            if !stable_cores.contains_key(line) {
+                let stable_core = fetch_stable_core(client, config, line)?;
+                stable_cores.insert(line.to_owned(), stable_core);
+            }
+
+            return Ok(resolve_stable_core(core, &stable_cores[line]));
+        }
+    };
+
+    let pinned_url = match pinned_core_url(core, target_core_version)? {
+        Some(pinned_url) => pinned_url,
+        None => {
+            return Ok(TargetCore {
+                version: target_core_version.to_owned(),
+                pinned_core: None,
+            })
+        }
+    };
+
+    let checksum_url = format!("{}{}", pinned_url, SHA256_EXT);
+
+    let checksum_str = with_retries(config.retry.as_ref(), || fetch_string(client, &checksum_url))
+        .chain_err(|| format!("Unable to fetch checksum of pinned core at '{}'", checksum_url))?;
+
+    // laid out as '<hex digest>  jenkins.war'
+    let sha256 = match checksum_str.split_whitespace().next() {
+        Some(hex) if hex.len() == 64 => base64::encode_block(&from_hex(hex)?),
+        _ => bail!(format!(
+            "Unable to find SHA-256 digest of pinned core at '{}'",
+            checksum_url
+        )),
+    };
+
+    let mut pinned_core = core.clone();
+    pinned_core.url = pinned_url;
+    pinned_core.version = target_core_version.to_owned();
+    pinned_core.sha256 = Some(sha256);
+
+    // the build date, size and SHA-1 all belong to the newer core
+    pinned_core.build_date = None;
+    pinned_core.sha1 = None;
+    pinned_core.extra.remove(SIZE_KEY);
+
+    Ok(TargetCore {
+        version: target_core_version.to_owned(),
+        pinned_core: Some(pinned_core),
+    })
+}
+
+fn resolve_stable_core(core: &Core, stable_core: &Core) -> TargetCore {
+    // an older upstream core is already compatible with the release
+    let is_newer = VersionNumber::parse(&core.version) > VersionNumber::parse(&stable_core.version);
+
+    TargetCore {
+        version: stable_core.version.to_owned(),
+        pinned_core: if is_newer { Some(stable_core.clone()) } else { None },
+    }
+}
+
+pub fn modify_update_center(
+    update_center: &mut UpdateCenter,
+    config: &ModConfig,
+    site: &SiteConfig,
+    target_core: Option<&TargetCore>,
+    plugin_versions: Option<&PluginVersions>,
+) -> Result<VariantOutput> {
+    // roots left out for their core still show up in the compatibility report
+    if !config.plugin_roots.is_empty() {
+        keep_plugin_closure(
+            update_center,
+            &config.plugin_roots,
+            config.include_optional_dependencies,
+        )?;
+    }
+
+    let mut incompatibilities = match target_core {
+        Some(target_core) => {
+            let incompatibilities = filter_plugins_by_core(
+                update_center,
+                &target_core.version,
+                config.keep_incompatible_plugins,
+            );
+
+            if let Some(ref pinned_core) = target_core.pinned_core {
+                pin_core(update_center, pinned_core);
+            }
+
+            incompatibilities
+        }
+        None => Vec::new(),
+    };
+
+    if !config.plugin_includes.is_empty() || !config.plugin_excludes.is_empty() {
+        filter_plugins(
+            update_center,
+            &config.plugin_includes,
+            &config.plugin_excludes,
+        )?;
+    }
+
+    let mut vulnerabilities = if config.check_security_warnings {
+        find_vulnerabilities(update_center, config.keep_vulnerable_plugins)?
+    } else {
+        Vec::new()
+    };
+
+    let is_pruned = (target_core.is_some() && !config.keep_incompatible_plugins)
+        || (config.check_security_warnings && !config.keep_vulnerable_plugins)
+        || !config.plugin_roots.is_empty()
+        || !config.plugin_includes.is_empty()
+        || !config.plugin_excludes.is_empty();
+
+    // pruning may leave behind plugins that can no longer be installed
+    if is_pruned {
+        let problems = check_plugin_dependencies(update_center);
+
+        for problem in &problems {
+            warn!("{}", problem);
+        }
+
+        if config.strict_dependency_check && !problems.is_empty() {
+            bail!(format!(
+                "Found {} unsatisfied plugin dependencies after filtering",
+                problems.len()
+            ));
+        }
+    }
+
+    update_center.connection_check_url = site.connection_check_url_change.to_owned();
+    let rewriter = UrlRewriter::new(site)?;
+    let core_url_entry = replace_core_url(update_center, &rewriter);
+    let mut plugin_urls = replace_plugin_urls(update_center, &rewriter);
+
+    if rewriter.has_scope(UrlScope::Warnings) {
+        replace_warning_urls(update_center, &rewriter);
+    }
+
+    let has_plugin_doc_scope = [UrlScope::Wiki, UrlScope::Scm, UrlScope::IssueTrackers]
+        .iter()
+        .any(|scope| rewriter.has_scope(*scope));
+
+    if has_plugin_doc_scope {
+        replace_plugin_doc_urls(update_center, &rewriter);
+    }
+
+    if rewriter.has_scope(UrlScope::Deprecations) {
+        replace_deprecation_urls(update_center, &rewriter);
+    }
+
+    let plugin_versions = match (&config.historical_versions, plugin_versions) {
+        (Some(historical_versions), Some(plugin_versions)) => {
+            let checks = VersionChecks {
+                target_core_version: target_core.map(|target_core| target_core.version.as_str()),
+                keep_incompatible_plugins: config.keep_incompatible_plugins,
+                check_security_warnings: config.check_security_warnings,
+                keep_vulnerable_plugins: config.keep_vulnerable_plugins,
+            };
+
+            let mut selected = select_plugin_versions(
+                update_center,
+                plugin_versions,
+                historical_versions,
+                &checks,
+                &rewriter,
+            )?;
+
+            plugin_urls.append(&mut selected.url_entries);
+            vulnerabilities.append(&mut selected.vulnerabilities);
+            incompatibilities.append(&mut selected.incompatibilities);
+            Some(selected.plugin_versions)
+        }
+        _ => None,
+    };
+
+    // signing must come last since it covers every other change
+    if let Some(ref signing) = config.signing {
+        sign_update_center(update_center, signing)?;
+        info!("Re-signed modified update-center JSON");
+    }
+
+    // combine both the core + plugin links
+    let mut urls = vec![core_url_entry];
+    urls.append(&mut plugin_urls);
+
+    Ok(VariantOutput {
+        urls,
+        vulnerabilities,
+        incompatibilities,
+        plugin_versions,
+        url_changes: rewriter.into_changes(),
+    })
+}
+
+/// Turns `update-center.json` into `update-center.actual.json`.
+fn actual_json_file_path(modified_json_file_path: &Path) -> Result<PathBuf> {
+    let file_stem = match modified_json_file_path.file_stem() {
+        Some(file_stem) => file_stem.to_string_lossy(),
+        None => bail!(format!(
+            "Expected {:?} to have a file name",
+            modified_json_file_path
+        )),
+    };
+
+    let file_name = match modified_json_file_path.extension() {
+        Some(ext) => format!("{}.{}.{}", file_stem, ACTUAL_JSON_INFIX, ext.to_string_lossy()),
+        None => format!("{}.{}", file_stem, ACTUAL_JSON_INFIX),
+    };
+
+    Ok(modified_json_file_path.with_file_name(file_name))
+}
+
+fn write_json_file(
+    outputs: &mut PendingOutputs,
+    json_file_path: &Path,
+    json_str: &str,
+    write_gzip_copy: bool,
+) -> Result<()> {
+    outputs.write_served(json_file_path, json_str.as_bytes())?;
+
+    if write_gzip_copy {
+        let mut gzip_file_name = json_file_path.as_os_str().to_owned();
+        gzip_file_name.push(GZIP_EXT);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+
+        let gzip_bytes = encoder
+            .write_all(json_str.as_bytes())
+            .and_then(|_| encoder.finish())
+            .chain_err(|| format!("Unable to gzip JSON of {:?}", json_file_path))?;
+
+        outputs.write_served(Path::new(&gzip_file_name), &gzip_bytes)?;
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_variant(
+    outputs: &mut PendingOutputs,
+    config: &ModConfig,
+    site: &SiteConfig,
+    update_center: &UpdateCenter,
+    envelope: &JsonpEnvelope,
+    plugin_versions: Option<&PluginVersions>,
+    target_core: Option<&TargetCore>,
+    modified_json_file_path: &Path,
+) -> Result<Vec<UrlEntry>> {
+    let mut update_center = update_center.clone();
+
+    let output = modify_update_center(
+        &mut update_center,
+        config,
+        site,
+        target_core,
+        plugin_versions,
+    )?;
+
+    let serialized_json = serde_json::to_string(&update_center)
+        .chain_err(|| "Unable to convert modified JSON back into string for serialization")?;
+
+    // need to append back the JSONP envelope
+    let wrapped_json = envelope.wrap(&serialized_json);
+
+    // write the modified JSON file, or to stdout for piping it elsewhere
+    if modified_json_file_path == Path::new(STDIO_PATH) {
+        outputs.write_stdout(wrapped_json.as_bytes());
+    } else {
+        write_json_file(
+            outputs,
+            modified_json_file_path,
+            &wrapped_json,
+            config.write_gzip_copies,
+        )?;
+
+        // upstream serves the unwrapped JSON alongside for Jenkins itself
+        if config.write_actual_json {
+            write_json_file(
+                outputs,
+                &actual_json_file_path(modified_json_file_path)?,
+                &serialized_json,
+                config.write_gzip_copies,
+            )?;
+        }
+    }
+
+    if let Some(ref plugin_versions) = output.plugin_versions {
+        let plugin_versions_file_path =
+            modified_json_file_path.with_file_name(PLUGIN_VERSIONS_FILE_NAME);
+
+        let plugin_versions_json = serde_json::to_string(plugin_versions)
+            .chain_err(|| "Unable to convert modified plugin versions JSON back into string")?;
+
+        outputs.write_served(&plugin_versions_file_path, plugin_versions_json.as_bytes())?;
+    }
+
+    if config.check_security_warnings {
+        let report_file_path = modified_json_file_path.with_file_name(SECURITY_REPORT_FILE_NAME);
+
+        let report_json = serde_json::to_string_pretty(&output.vulnerabilities)
+            .chain_err(|| "Unable to convert security report into pretty JSON form")?;
+
+        outputs.write(&report_file_path, report_json.as_bytes())?;
+    }
+
+    if target_core.is_some() {
+        let report_file_path =
+            modified_json_file_path.with_file_name(COMPATIBILITY_REPORT_FILE_NAME);
+
+        let report_json = serde_json::to_string_pretty(&output.incompatibilities)
+            .chain_err(|| "Unable to convert compatibility report into pretty JSON form")?;
+
+        outputs.write(&report_file_path, report_json.as_bytes())?;
+    }
+
+    if config.write_url_rewrite_report {
+        let report_file_path =
+            modified_json_file_path.with_file_name(URL_REWRITE_REPORT_FILE_NAME);
+
+        let report_json = serde_json::to_string_pretty(&output.url_changes)
+            .chain_err(|| "Unable to convert URL rewrite report into pretty JSON form")?;
+
+        outputs.write(&report_file_path, report_json.as_bytes())?;
+    }
+
+    Ok(output.urls)
+}
+
+fn write_url_list(
+    outputs: &mut PendingOutputs,
+    url_list_json_file_path: &Path,
+    urls: Vec<UrlEntry>,
+) -> Result<Vec<UrlEntry>> {
+    // artifacts shared between sites only need to be synced once
+    let url_count = urls.len();
+    let urls = dedup_url_entries(urls);
+
+    if urls.len() < url_count {
+        info!(
+            "Removed {} duplicate URLs from {:?}",
+            url_count - urls.len(),
+            url_list_json_file_path
+        );
+    }
+
+    let urls_json = serde_json::to_string_pretty(&urls)
+        .chain_err(|| "Unable to convert list of URLs into pretty JSON form")?;
+
+    outputs.write(url_list_json_file_path, urls_json.as_bytes())?;
+    Ok(urls)
+}
+
+/// Outcome of `run_mod`, along with what it saw upstream, which is only to
+/// be remembered once the outputs have been made use of.
+pub struct ModRun {
+    /// URL lists keyed by their output paths, or `None` if nothing has
+    /// changed and nothing was written.
+    pub url_lists: Option<BTreeMap<PathBuf, Vec<UrlEntry>>>,
+    state: ModState,
+}
+
+impl ModRun {
+    /// Saves the state into `state_file_path`, if there is one, for the next
+    /// run to compare against.
+    pub fn save_state(&self, config: &ModConfig) -> Result<()> {
+        match config.state_file_path {
+            Some(ref state_file_path) => self.state.save(state_file_path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fetches and modifies the update-center of every site and core variant,
+/// then writes out the modified JSON files and their URL lists, which are
+/// also returned keyed by their output paths. Writes nothing if
+/// `state_file_path` shows that no site has changed.
+pub fn run_mod(config: &ModConfig) -> Result<ModRun> {
+    let client = new_client()?;
+
+    // core variants are named after the default site output, which stdout has not
+    if config.modified_json_file_path == Path::new(STDIO_PATH) && !config.core_variants.is_empty() {
+        bail!(format!(
+            "Writing the modified JSON to stdout ('{}') cannot be combined with 'core_variants'",
+            STDIO_PATH
+        ));
+    }
+
+    // invalid core targets are reported before anything is fetched
+    let core_target = config.core_target()?;
+
+    for core_variant in &config.core_variants {
+        core_variant.core_target()?;
+    }
+
+    let sites = config.all_sites();
+
+    // files written next to the modified JSON have nowhere to go with stdout
+    let is_stdout_used = sites
+        .iter()
+        .any(|site| site.modified_json_file_path == Path::new(STDIO_PATH));
+
+    let has_side_files = config.historical_versions.is_some()
+        || config.check_security_warnings
+        || core_target.is_some()
+        || config.write_url_rewrite_report;
+
+    if is_stdout_used && has_side_files {
+        bail!(format!(
+            "Writing the modified JSON to stdout ('{}') cannot be combined with \
+             'historical_versions', 'check_security_warnings', a target core or \
+             'write_url_rewrite_report', which write files next to it",
+            STDIO_PATH
+        ));
+    }
+
+    let previous_state = match config.state_file_path {
+        Some(ref state_file_path) => ModState::load(state_file_path)?,
+        None => ModState::default(),
+    };
+
+    let mut state = ModState {
+        config_digest: Some(config_digest(config)?),
+        sites: BTreeMap::new(),
+    };
+
+    // without a state file, every run counts as a change
+    let mut is_changed =
+        config.state_file_path.is_none() || state.config_digest != previous_state.config_digest;
+
+    let mut update_centers = Vec::new();
+
+    for site in &sites {
+        let previous_site_state = previous_state
+            .sites
+            .get(&site.name)
+            .cloned()
+            .unwrap_or_default();
+
+        let fetched = with_retries(config.retry.as_ref(), || {
+            fetch_string_if_modified(
+                &client,
+                &site.update_center_url,
+                &previous_site_state.validators,
+            )
+        })?;
+
+        let update_center = match fetched {
+            ConditionalFetch::NotModified => {
+                info!("Site '{}' is not modified since the last run", site.name);
+                state.sites.insert(site.name.to_owned(), previous_site_state);
+                None
+            }
+            ConditionalFetch::Modified(resp_str, validators) => {
+                let (update_center, envelope) =
+                    parse_update_center(&resp_str, site, config.verification.as_ref())?;
+
+                let generation_timestamp =
+                    update_center.extra.get(GENERATION_TIMESTAMP_KEY).cloned();
+
+                // servers without validators still give away unchanged content
+                if generation_timestamp.is_none()
+                    || generation_timestamp != previous_site_state.generation_timestamp
+                {
+                    is_changed = true;
+                }
+
+                let site_state = SiteState {
+                    validators,
+                    generation_timestamp,
+                };
+
+                state.sites.insert(site.name.to_owned(), site_state);
+                Some((update_center, envelope))
+            }
+        };
+
+        update_centers.push(update_center);
+    }
+
+    if !is_changed {
+        info!("No site has changed since the last run, leaving outputs untouched");
+
+        return Ok(ModRun {
+            url_lists: None,
+            state,
+        });
+    }
+
+    let plugin_versions = match config.historical_versions {
+        Some(ref historical_versions) => {
+            Some(with_retries(config.retry.as_ref(), || {
+                fetch_plugin_versions(&client, historical_versions)
+            })?)
+        }
+        None => None,
+    };
+
+    // nothing is published until every output has been written in full
+    let mut outputs = PendingOutputs::new(
+        config.auto_create_output_dir,
+        config.staging_dir_path.as_deref(),
+    );
+
+    // URL lists combined across all sites, keyed by their output paths
+    let mut url_lists: BTreeMap<PathBuf, Vec<UrlEntry>> = BTreeMap::new();
+
+    // LTS cores by line, shared by every site
+    let mut stable_cores = BTreeMap::new();
+
+    for (site_index, (site, update_center)) in sites.iter().zip(update_centers).enumerate() {
+        info!(
+            "Processing site '{}' from '{}'",
+            site.name,
+            site.update_center_url
+        );
+
+        // a site not modified upstream still needs its content if another one changed
+        let (update_center, envelope) = match update_center {
+            Some(update_center) => update_center,
+            None => with_retries(config.retry.as_ref(), || {
+                fetch_update_center(&client, site, config.verification.as_ref())
+            })?,
+        };
+
+        let target_core = match core_target {
+            Some(core_target) => Some(resolve_target_core(
+                &client,
+                config,
+                &mut stable_cores,
+                &update_center.core,
+                core_target,
+            )?),
+            None => None,
+        };
+
+        let urls = write_variant(
+            &mut outputs,
+            config,
+            site,
+            &update_center,
+            &envelope,
+            plugin_versions.as_ref(),
+            target_core.as_ref(),
+            &site.modified_json_file_path,
+        )?;
+
+        url_lists
+            .entry(config.url_list_json_file_path.to_owned())
+            .or_default()
+            .extend(urls);
+
+        // every core variant is derived from the same upstream fetch
+        for core_variant in &config.core_variants {
+            let variant_file_path = |file_path: &Path, is_site_file: bool| -> Result<PathBuf> {
+                let file_name = match file_path.file_name() {
+                    Some(file_name) => file_name,
+                    None => bail!(format!("Expected {:?} to have a file name", file_path)),
+                };
+
+                // extra sites are kept apart in their own sub-directory
+                if is_site_file && site_index > 0 {
+                    Ok(core_variant.output_dir_path.join(&site.name).join(file_name))
+                } else {
+                    Ok(core_variant.output_dir_path.join(file_name))
+                }
+            };
+
+            let variant_core_target = core_variant.core_target()?;
+
+            info!(
+                "Writing core variant {} of site '{}' into {:?}",
+                variant_core_target,
+                site.name,
+                core_variant.output_dir_path
+            );
+
+            let target_core = resolve_target_core(
+                &client,
+                config,
+                &mut stable_cores,
+                &update_center.core,
+                variant_core_target,
+            )?;
+
+            let urls = write_variant(
+                &mut outputs,
+                config,
+                site,
+                &update_center,
+                &envelope,
+                plugin_versions.as_ref(),
+                Some(&target_core),
+                &variant_file_path(&site.modified_json_file_path, true)?,
+            )?;
+
+            url_lists
+                .entry(variant_file_path(&config.url_list_json_file_path, false)?)
+                .or_default()
+                .extend(urls.iter().cloned());
+
+            // the combined list lets a single sync mirror every variant
+            url_lists
+                .entry(config.url_list_json_file_path.to_owned())
+                .or_default()
+                .extend(urls);
+        }
+    }
+
+    let url_lists = url_lists
+        .into_iter()
+        .map(|(url_list_json_file_path, urls)| {
+            write_url_list(&mut outputs, &url_list_json_file_path, urls)
+                .map(|urls| (url_list_json_file_path, urls))
+        })
+        .collect::<Result<_>>()?;
+
+    outputs.commit()?;
+
+    Ok(ModRun {
+        url_lists: Some(url_lists),
+        state,
+    })
+}
@@ -0,0 +1,431 @@
+use errors::*;
+use fetch::read_file_into_string;
+use serde_json;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+// lists the staged files and where they go live, written into the staging
+// directory once every staged file is in place
+const STAGING_MANIFEST_FILE_NAME: &str = "staging-manifest.json";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StagedFile {
+    staged_file_path: PathBuf,
+    live_file_path: PathBuf,
+}
+
+/// Output files of a run, each written and fsynced under a temp name next
+/// to its final path, and only renamed into place together by `commit`, so
+/// that readers never see a half-written file. Temp files left behind by a
+/// failed run are removed on drop.
+pub struct PendingOutputs {
+    auto_create_output_dir: bool,
+    staging_dir_path: Option<PathBuf>,
+    pending: Vec<(PathBuf, PathBuf)>,
+    staged: Vec<StagedFile>,
+    stdout: Vec<u8>,
+}
+
+impl PendingOutputs {
+    pub fn new(auto_create_output_dir: bool, staging_dir_path: Option<&Path>) -> PendingOutputs {
+        PendingOutputs {
+            auto_create_output_dir,
+            staging_dir_path: staging_dir_path.map(Path::to_owned),
+            pending: Vec::new(),
+            staged: Vec::new(),
+            stdout: Vec::new(),
+        }
+    }
+
+    /// Holds back output for stdout until every file has been committed,
+    /// so that a failed run does not emit anything.
+    pub fn write_stdout(&mut self, bytes: &[u8]) {
+        self.stdout.extend_from_slice(bytes);
+    }
+
+    pub fn write(&mut self, file_path: &Path, bytes: &[u8]) -> Result<()> {
+        let auto_create_output_dir = self.auto_create_output_dir;
+        self.write_file(file_path, bytes, auto_create_output_dir)
+    }
+
+    /// Writes a file served to Jenkins, which goes into the staging directory
+    /// instead if there is one, until `promote_staged` publishes it.
+    pub fn write_served(&mut self, file_path: &Path, bytes: &[u8]) -> Result<()> {
+        let staged_file_path = match self.staging_dir_path {
+            Some(ref staging_dir_path) => staged_file_path(staging_dir_path, file_path)?,
+            None => return self.write(file_path, bytes),
+        };
+
+        self.write_file(&staged_file_path, bytes, true)?;
+
+        // absolute, as the promotion may run from another working directory
+        let staged_file_path = absolute_path(&staged_file_path)?;
+        let live_file_path = absolute_path(file_path)?;
+
+        if !self
+            .staged
+            .iter()
+            .any(|staged_file| staged_file.live_file_path == live_file_path)
+        {
+            self.staged.push(StagedFile {
+                staged_file_path,
+                live_file_path,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn write_file(
+        &mut self,
+        file_path: &Path,
+        bytes: &[u8],
+        create_parent_dir: bool,
+    ) -> Result<()> {
+        if create_parent_dir {
+            create_parent_dir_if_present(file_path.parent())?;
+        }
+
+        let temp_file_path = temp_file_path(file_path)?;
+
+        let mut temp_file = File::create(&temp_file_path)
+            .chain_err(|| format!("Unable to open {:?} for writing", temp_file_path))?;
+
+        // tracked before writing so that a partial temp file is cleaned up too,
+        // where a later write to the same path simply replaces the earlier one
+        if !self.pending.iter().any(|(_, pending_path)| pending_path == file_path) {
+            self.pending
+                .push((temp_file_path.to_owned(), file_path.to_owned()));
+        }
+
+        temp_file
+            .write_all(bytes)
+            .and_then(|_| temp_file.sync_all())
+            .chain_err(|| format!("Unable to write and sync {:?}", temp_file_path))
+    }
+
+    /// Renames every pending file into place, in the order written, with
+    /// the staging manifest last.
+    pub fn commit(mut self) -> Result<()> {
+        if let Some(staging_dir_path) = self.staging_dir_path.clone() {
+            if !self.staged.is_empty() {
+                let manifest_json = serde_json::to_string_pretty(&self.staged)
+                    .chain_err(|| "Unable to convert staging manifest into pretty JSON form")?;
+
+                let manifest_file_path = staging_dir_path.join(STAGING_MANIFEST_FILE_NAME);
+                self.write_file(&manifest_file_path, manifest_json.as_bytes(), true)?;
+            }
+        }
+
+        let pending: Vec<_> = self.pending.drain(..).collect();
+
+        for (i, (temp_file_path, file_path)) in pending.iter().enumerate() {
+            if let Err(e) = fs::rename(temp_file_path, file_path) {
+                // nothing renamed yet should linger as a temp file
+                for (temp_file_path, _) in &pending[i..] {
+                    let _ = fs::remove_file(temp_file_path);
+                }
+
+                return Err(e).chain_err(|| format!("Unable to rename into {:?}", file_path));
+            }
+
+            sync_parent_dir(file_path);
+        }
+
+        if !self.stdout.is_empty() {
+            let stdout = &mut io::stdout();
+
+            stdout
+                .write_all(&self.stdout)
+                .and_then(|_| stdout.flush())
+                .chain_err(|| "Unable to write into stdout")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for PendingOutputs {
+    fn drop(&mut self) {
+        for (temp_file_path, _) in &self.pending {
+            if let Err(e) = fs::remove_file(temp_file_path) {
+                error!("Unable to remove temp file {:?}: {}", temp_file_path, e);
+            }
+        }
+    }
+}
+
+/// Publishes the files staged by a previous run into their live paths,
+/// which must be on the same file system as the staging directory.
+pub fn promote_staged(staging_dir_path: &Path) -> Result<()> {
+    let manifest_file_path = staging_dir_path.join(STAGING_MANIFEST_FILE_NAME);
+
+    if !manifest_file_path.exists() {
+        info!("Nothing staged in {:?} to promote", staging_dir_path);
+        return Ok(());
+    }
+
+    let manifest_str = read_file_into_string(&manifest_file_path)?;
+
+    let staged_files: Vec<StagedFile> = serde_json::from_str(&manifest_str).chain_err(|| {
+        format!(
+            "Unable to parse staging manifest at {:?}",
+            manifest_file_path
+        )
+    })?;
+
+    for staged_file in &staged_files {
+        // an earlier promotion may have been cut short after this file
+        if !staged_file.staged_file_path.exists() {
+            warn!(
+                "Staged file {:?} is missing, assuming it has been promoted already",
+                staged_file.staged_file_path
+            );
+
+            continue;
+        }
+
+        create_parent_dir_if_present(staged_file.live_file_path.parent())?;
+
+        fs::rename(&staged_file.staged_file_path, &staged_file.live_file_path).chain_err(|| {
+            format!(
+                "Unable to promote {:?} into {:?}",
+                staged_file.staged_file_path,
+                staged_file.live_file_path
+            )
+        })?;
+
+        sync_parent_dir(&staged_file.live_file_path);
+
+        info!(
+            "Promoted {:?} into {:?}",
+            staged_file.staged_file_path,
+            staged_file.live_file_path
+        );
+    }
+
+    fs::remove_file(&manifest_file_path)
+        .chain_err(|| format!("Unable to remove staging manifest at {:?}", manifest_file_path))
+}
+
+/// Whether a previous run has staged files that are still to be promoted.
+pub fn has_staged(staging_dir_path: &Path) -> bool {
+    staging_dir_path.join(STAGING_MANIFEST_FILE_NAME).exists()
+}
+
+/// Mirrors `file_path` under `staging_dir_path`, so that files of the same
+/// name in different directories do not clash.
+fn staged_file_path(staging_dir_path: &Path, file_path: &Path) -> Result<PathBuf> {
+    let mut staged_file_path = staging_dir_path.to_owned();
+
+    for component in file_path.components() {
+        match component {
+            Component::Normal(component) => staged_file_path.push(component),
+            Component::ParentDir => bail!(format!(
+                "Unable to stage {:?} as it refers to a parent directory",
+                file_path
+            )),
+            _ => (),
+        }
+    }
+
+    Ok(staged_file_path)
+}
+
+/// Resolves `file_path` through its canonicalized parent directory, or
+/// against the working directory while that does not exist yet.
+fn absolute_path(file_path: &Path) -> Result<PathBuf> {
+    let file_name = match file_path.file_name() {
+        Some(file_name) => file_name,
+        None => bail!(format!("Expected a file name in {:?}", file_path)),
+    };
+
+    let parent_dir_path = match file_path.parent() {
+        Some(parent_dir_path) if parent_dir_path != Path::new("") => parent_dir_path,
+        _ => Path::new("."),
+    };
+
+    match fs::canonicalize(parent_dir_path) {
+        Ok(parent_dir_path) => Ok(parent_dir_path.join(file_name)),
+        Err(_) => env::current_dir()
+            .map(|current_dir_path| current_dir_path.join(file_path))
+            .chain_err(|| format!("Unable to resolve {:?} into an absolute path", file_path)),
+    }
+}
+
+/// `dir/.name.<pid>.tmp` for `dir/name`, on the same file system so that
+/// the rename is atomic.
+fn temp_file_path(file_path: &Path) -> Result<PathBuf> {
+    let file_name = match file_path.file_name() {
+        Some(file_name) => file_name.to_string_lossy(),
+        None => bail!(format!("Expected {:?} to have a file name", file_path)),
+    };
+
+    Ok(file_path.with_file_name(format!(".{}.{}.tmp", file_name, process::id())))
+}
+
+// the rename itself only survives a crash once the directory is synced,
+// which not every platform allows, hence best effort
+fn sync_parent_dir(file_path: &Path) {
+    let parent_dir = match file_path.parent() {
+        Some(parent_dir) if parent_dir != Path::new("") => parent_dir,
+        _ => Path::new("."),
+    };
+
+    if let Ok(dir) = File::open(parent_dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+pub(crate) fn create_parent_dir_if_present(dir_opt: Option<&Path>) -> Result<()> {
+    let dir_opt = dir_opt.and_then(|dir| {
+        // ignore if the directory has already been created
+        if Path::new(dir).is_dir() {
+            None
+        } else {
+            Some(dir)
+        }
+    });
+
+    match dir_opt {
+        Some(dir) => {
+            info!("Creating directory chain: {:?}", dir);
+
+            fs::create_dir_all(dir)
+                .chain_err(|| format!("Unable to create directory chain: {:?}", dir))
+        }
+
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh output directory of its own
+    fn output_dir(name: &str) -> PathBuf {
+        let dir_path = env::temp_dir().join(format!(
+            "jenkins-update-mod-publish-{}-{}",
+            name,
+            process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir_path);
+        fs::create_dir_all(&dir_path).unwrap();
+        dir_path
+    }
+
+    fn file_names(dir_path: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn renames_outputs_into_place_on_commit() {
+        let output_dir = output_dir("commit");
+        let json_path = output_dir.join("update-center.json");
+        let report_path = output_dir.join("reports/security-report.json");
+        fs::write(&json_path, b"old").unwrap();
+
+        let mut outputs = PendingOutputs::new(true, None);
+        outputs.write(&json_path, b"first").unwrap();
+        outputs.write(&report_path, b"[]").unwrap();
+        // a later write to the same path replaces the earlier one
+        outputs.write(&json_path, b"new").unwrap();
+
+        assert_eq!(fs::read(&json_path).unwrap(), b"old");
+        assert!(!report_path.exists());
+
+        outputs.commit().unwrap();
+
+        assert_eq!(fs::read(&json_path).unwrap(), b"new");
+        assert_eq!(fs::read(&report_path).unwrap(), b"[]");
+        assert_eq!(file_names(&output_dir), vec!["reports", "update-center.json"]);
+    }
+
+    #[test]
+    fn removes_temp_files_without_commit() {
+        let output_dir = output_dir("drop");
+        let json_path = output_dir.join("update-center.json");
+        fs::write(&json_path, b"old").unwrap();
+
+        let mut outputs = PendingOutputs::new(true, None);
+        outputs.write(&json_path, b"new").unwrap();
+        drop(outputs);
+
+        assert_eq!(fs::read(&json_path).unwrap(), b"old");
+        assert_eq!(file_names(&output_dir), vec!["update-center.json"]);
+    }
+
+    #[test]
+    fn stages_served_files_until_promoted() {
+        let output_dir = output_dir("stage");
+        let staging_dir = output_dir.join("staging");
+        let json_path = output_dir.join("www/update-center.json");
+        let list_path = output_dir.join("url-list.json");
+        fs::create_dir_all(json_path.parent().unwrap()).unwrap();
+        fs::write(&json_path, b"old").unwrap();
+
+        let mut outputs = PendingOutputs::new(true, Some(&staging_dir));
+        outputs.write_served(&json_path, b"new").unwrap();
+        outputs.write(&list_path, b"[]").unwrap();
+        outputs.commit().unwrap();
+
+        // only what Jenkins is served waits for the promotion
+        assert_eq!(fs::read(&json_path).unwrap(), b"old");
+        assert_eq!(fs::read(&list_path).unwrap(), b"[]");
+        assert!(has_staged(&staging_dir));
+        assert!(staged_file_path(&staging_dir, &json_path).unwrap().exists());
+
+        promote_staged(&staging_dir).unwrap();
+
+        assert_eq!(fs::read(&json_path).unwrap(), b"new");
+        assert!(!has_staged(&staging_dir));
+
+        // nothing left to promote
+        promote_staged(&staging_dir).unwrap();
+        assert_eq!(fs::read(&json_path).unwrap(), b"new");
+    }
+
+    #[test]
+    fn resumes_interrupted_promotions() {
+        let output_dir = output_dir("resume");
+        let staging_dir = output_dir.join("staging");
+        let first_path = output_dir.join("first.json");
+        let second_path = output_dir.join("second.json");
+
+        let mut outputs = PendingOutputs::new(true, Some(&staging_dir));
+        outputs.write_served(&first_path, b"first").unwrap();
+        outputs.write_served(&second_path, b"second").unwrap();
+        outputs.commit().unwrap();
+
+        // as if a promotion had been cut short after the first file
+        fs::rename(staged_file_path(&staging_dir, &first_path).unwrap(), &first_path).unwrap();
+
+        promote_staged(&staging_dir).unwrap();
+
+        assert_eq!(fs::read(&first_path).unwrap(), b"first");
+        assert_eq!(fs::read(&second_path).unwrap(), b"second");
+        assert!(!has_staged(&staging_dir));
+    }
+
+    #[test]
+    fn refuses_to_stage_outside_of_the_staging_dir() {
+        let staging_dir = Path::new("staging");
+
+        assert_eq!(
+            staged_file_path(staging_dir, Path::new("/srv/www/update-center.json")).unwrap(),
+            Path::new("staging/srv/www/update-center.json")
+        );
+        assert!(staged_file_path(staging_dir, Path::new("../update-center.json")).is_err());
+    }
+}
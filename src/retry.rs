@@ -0,0 +1,169 @@
+//! Retrying of transient failures, such as dropped connections and
+//! overloaded servers, with jittered exponential backoff.
+
+use chrono::{DateTime, Utc};
+use config::RetryConfig;
+use errors::*;
+use hyper;
+use hyper::client::{RequestBuilder, Response};
+use hyper::status::StatusCode;
+use openssl::rand::rand_bytes;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+const RETRY_AFTER_HEADER: &str = "Retry-After";
+
+/// Runs `f` until it succeeds, fails with anything but a transient error, or
+/// has been attempted `max_attempts` times. Without `retry`, `f` is only
+/// attempted once.
+pub(crate) fn with_retries<T, F>(retry: Option<&RetryConfig>, mut f: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let max_attempts = retry.map_or(1, |retry| retry.max_attempts.max(1));
+    let mut attempt = 1;
+
+    loop {
+        let e = match f() {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        let retry_after = match *e.kind() {
+            ErrorKind::Transient(_, retry_after) => retry_after,
+            _ => return Err(e),
+        };
+
+        let retry = match retry {
+            Some(retry) if attempt < max_attempts => retry,
+            _ => return Err(e),
+        };
+
+        // retrying any sooner would only be turned away again
+        if let Some(retry_after) = retry_after {
+            if retry_after > Duration::from_millis(retry.max_delay_ms) {
+                warn!(
+                    "{}, not retrying as Retry-After of {:?} exceeds max_delay_ms",
+                    e,
+                    retry_after
+                );
+
+                return Err(e);
+            }
+        }
+
+        let delay = retry_delay(retry, attempt, retry_after);
+
+        warn!(
+            "{}, retrying in {:?} (attempt {} of {})",
+            e,
+            delay,
+            attempt + 1,
+            max_attempts
+        );
+
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Error for `e` described by `msg`, which is transient if the connection
+/// dropped or stalled, rather than it being unusable to begin with.
+pub(crate) fn io_error(msg: String, e: &io::Error) -> Error {
+    let is_transient = matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+    );
+
+    let msg = format!("{}: {}", msg, e);
+
+    if is_transient {
+        ErrorKind::Transient(msg, None).into()
+    } else {
+        msg.into()
+    }
+}
+
+/// Sends the request, failing with a transient error if the connection
+/// fails or the server answers with a status worth retrying.
+pub(crate) fn send_request(req: RequestBuilder, url: &str) -> Result<Response> {
+    let resp = match req.send() {
+        Ok(resp) => resp,
+        Err(hyper::Error::Io(e)) => {
+            return Err(io_error(
+                format!("Unable to perform HTTP request with URL '{}'", url),
+                &e,
+            ))
+        }
+        Err(e) => {
+            return Err(e)
+                .chain_err(|| format!("Unable to perform HTTP request with URL '{}'", url))
+        }
+    };
+
+    let is_transient = match resp.status {
+        StatusCode::RequestTimeout | StatusCode::TooManyRequests => true,
+        status => status.is_server_error(),
+    };
+
+    if is_transient {
+        bail!(ErrorKind::Transient(
+            format!("HTTP request with URL '{}' failed with {}", url, resp.status),
+            retry_after(&resp)
+        ));
+    }
+
+    Ok(resp)
+}
+
+/// Backs off exponentially from `initial_delay_ms` up to `max_delay_ms`,
+/// picking at random from the upper half of the delay so that parallel
+/// downloads spread out. A `Retry-After` of the server is taken as is.
+fn retry_delay(retry: &RetryConfig, attempt: u32, retry_after: Option<Duration>) -> Duration {
+    if let Some(retry_after) = retry_after {
+        return retry_after;
+    }
+
+    let delay_ms = retry
+        .initial_delay_ms
+        .saturating_mul(1u64.checked_shl(attempt - 1).unwrap_or(u64::MAX))
+        .min(retry.max_delay_ms);
+
+    let mut random_bytes = [0; 8];
+
+    let jitter_ms = match rand_bytes(&mut random_bytes) {
+        Ok(_) => u64::from_le_bytes(random_bytes) % (delay_ms / 2 + 1),
+        Err(_) => 0,
+    };
+
+    Duration::from_millis(delay_ms - delay_ms / 2 + jitter_ms)
+}
+
+// either a number of seconds or an HTTP date
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let value = resp.headers.get_raw(RETRY_AFTER_HEADER)?.first()?;
+    let value = String::from_utf8_lossy(value);
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let retry_at = DateTime::parse_from_rfc2822(value).ok()?;
+
+    // a date in the past means retrying right away
+    Some(
+        retry_at
+            .with_timezone(&Utc)
+            .signed_duration_since(Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
@@ -0,0 +1,369 @@
+use config::SiteConfig;
+use errors::*;
+use model::*;
+use regex::{self, Regex};
+use std::cell::RefCell;
+
+// URL rewrite rule scope names
+const ALL_SCOPE: &str = "all";
+const CORE_SCOPE: &str = "core";
+const PLUGINS_SCOPE: &str = "plugins";
+const WARNINGS_SCOPE: &str = "warnings";
+const WIKI_SCOPE: &str = "wiki";
+const SCM_SCOPE: &str = "scm";
+const ISSUE_TRACKERS_SCOPE: &str = "issue_trackers";
+const DEPRECATIONS_SCOPE: &str = "deprecations";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UrlScope {
+    Core,
+    Plugins,
+    Warnings,
+    Wiki,
+    Scm,
+    IssueTrackers,
+    Deprecations,
+}
+
+const ALL_URL_SCOPES: [UrlScope; 7] = [
+    UrlScope::Core,
+    UrlScope::Plugins,
+    UrlScope::Warnings,
+    UrlScope::Wiki,
+    UrlScope::Scm,
+    UrlScope::IssueTrackers,
+    UrlScope::Deprecations,
+];
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UrlChange {
+    pub field: String,
+    pub from: String,
+    pub into: String,
+}
+
+struct UrlRewriteRule {
+    regex: Regex,
+    replacement: String,
+    scopes: Vec<UrlScope>,
+}
+
+/// Ordered URL rewrite rules of a site, starting with the literal
+/// `url_replace_from` -> `url_replace_into` replacement if it is non-empty.
+pub(crate) struct UrlRewriter {
+    rules: Vec<UrlRewriteRule>,
+    apply_all: bool,
+    changes: RefCell<Vec<UrlChange>>,
+}
+
+impl UrlRewriter {
+    pub(crate) fn new(site: &SiteConfig) -> Result<UrlRewriter> {
+        let mut rules = Vec::new();
+
+        if !site.url_replace_from.is_empty() {
+            rules.push(UrlRewriteRule {
+                regex: Regex::new(&regex::escape(&site.url_replace_from))
+                    .chain_err(|| "Unable to escape 'url_replace_from' into regex")?,
+                replacement: site.url_replace_into.replace('$', "$$"),
+                scopes: vec![UrlScope::Core, UrlScope::Plugins],
+            });
+        }
+
+        for rule in &site.url_rewrite_rules {
+            let scopes = match rule.scope.as_deref() {
+                None | Some(ALL_SCOPE) => ALL_URL_SCOPES.to_vec(),
+                Some(CORE_SCOPE) => vec![UrlScope::Core],
+                Some(PLUGINS_SCOPE) => vec![UrlScope::Plugins],
+                Some(WARNINGS_SCOPE) => vec![UrlScope::Warnings],
+                Some(WIKI_SCOPE) => vec![UrlScope::Wiki],
+                Some(SCM_SCOPE) => vec![UrlScope::Scm],
+                Some(ISSUE_TRACKERS_SCOPE) => vec![UrlScope::IssueTrackers],
+                Some(DEPRECATIONS_SCOPE) => vec![UrlScope::Deprecations],
+                Some(scope) => bail!(format!(
+                    "Unknown URL rewrite scope '{}', expected one of {:?}",
+                    scope,
+                    [
+                        CORE_SCOPE,
+                        PLUGINS_SCOPE,
+                        WARNINGS_SCOPE,
+                        WIKI_SCOPE,
+                        SCM_SCOPE,
+                        ISSUE_TRACKERS_SCOPE,
+                        DEPRECATIONS_SCOPE,
+                        ALL_SCOPE,
+                    ]
+                )),
+            };
+
+            let regex = Regex::new(&rule.pattern)
+                .chain_err(|| format!("Unable to parse URL rewrite pattern '{}'", rule.pattern))?;
+
+            rules.push(UrlRewriteRule {
+                regex,
+                replacement: rule.replacement.to_owned(),
+                scopes,
+            });
+        }
+
+        Ok(UrlRewriter {
+            rules,
+            apply_all: site.apply_all_url_rewrite_rules,
+            changes: RefCell::new(Vec::new()),
+        })
+    }
+
+    pub(crate) fn has_scope(&self, scope: UrlScope) -> bool {
+        self.rules.iter().any(|rule| rule.scopes.contains(&scope))
+    }
+
+    /// Rewrites the URL found at `field`, recording the change if any.
+    fn rewrite(&self, field: &str, orig_url: &str, scope: UrlScope) -> String {
+        let mut url = orig_url.to_owned();
+
+        for rule in self.rules.iter().filter(|rule| rule.scopes.contains(&scope)) {
+            if rule.regex.is_match(&url) {
+                url = rule.regex.replace_all(&url, rule.replacement.as_str()).into_owned();
+
+                if !self.apply_all {
+                    break;
+                }
+            }
+        }
+
+        if url != orig_url {
+            self.changes.borrow_mut().push(UrlChange {
+                field: field.to_owned(),
+                from: orig_url.to_owned(),
+                into: url.to_owned(),
+            });
+        }
+
+        url
+    }
+
+    pub(crate) fn rewrite_field(
+        &self,
+        url: &mut String,
+        obj_path: &str,
+        key: &str,
+        scope: UrlScope,
+    ) {
+        *url = self.rewrite(&format!("{}.{}", obj_path, key), url, scope);
+    }
+
+    pub(crate) fn rewrite_optional_field(
+        &self,
+        url: &mut Option<String>,
+        obj_path: &str,
+        key: &str,
+        scope: UrlScope,
+    ) {
+        if let Some(ref mut url) = *url {
+            self.rewrite_field(url, obj_path, key, scope);
+        }
+    }
+
+    pub(crate) fn into_changes(self) -> Vec<UrlChange> {
+        self.changes.into_inner()
+    }
+}
+
+pub(crate) fn replace_core_url(
+    update_center: &mut UpdateCenter,
+    rewriter: &UrlRewriter,
+) -> UrlEntry {
+    let core = &mut update_center.core;
+
+    // checksums are carried along so that jenkins-sync can verify the downloads
+    let url_entry = core.url_entry();
+    rewriter.rewrite_field(&mut core.url, CORE_KEY, URL_KEY, UrlScope::Core);
+    url_entry
+}
+
+pub(crate) fn replace_plugin_urls(
+    update_center: &mut UpdateCenter,
+    rewriter: &UrlRewriter,
+) -> Vec<UrlEntry> {
+    update_center
+        .plugins
+        .iter_mut()
+        .map(|(plugin_name, plugin)| {
+            let plugin_path = format!("{}.{}", PLUGINS_KEY, plugin_name);
+            let url_entry = plugin.url_entry();
+            rewriter.rewrite_field(&mut plugin.url, &plugin_path, URL_KEY, UrlScope::Plugins);
+            url_entry
+        })
+        .collect()
+}
+
+pub(crate) fn replace_warning_urls(update_center: &mut UpdateCenter, rewriter: &UrlRewriter) {
+    for (i, warning) in update_center.warnings.iter_mut().flatten().enumerate() {
+        let warning_path = format!("{}[{}]", WARNINGS_KEY, i);
+        rewriter.rewrite_field(&mut warning.url, &warning_path, URL_KEY, UrlScope::Warnings);
+    }
+}
+
+pub(crate) fn replace_plugin_doc_urls(update_center: &mut UpdateCenter, rewriter: &UrlRewriter) {
+    for (plugin_name, plugin) in &mut update_center.plugins {
+        let plugin_path = format!("{}.{}", PLUGINS_KEY, plugin_name);
+
+        rewriter.rewrite_optional_field(&mut plugin.wiki, &plugin_path, WIKI_KEY, UrlScope::Wiki);
+        rewriter.rewrite_optional_field(&mut plugin.scm, &plugin_path, SCM_KEY, UrlScope::Scm);
+
+        for (i, issue_tracker) in plugin.issue_trackers.iter_mut().flatten().enumerate() {
+            let issue_tracker_path = format!("{}.{}[{}]", plugin_path, ISSUE_TRACKERS_KEY, i);
+
+            rewriter.rewrite_optional_field(
+                &mut issue_tracker.view_url,
+                &issue_tracker_path,
+                VIEW_URL_KEY,
+                UrlScope::IssueTrackers,
+            );
+
+            rewriter.rewrite_optional_field(
+                &mut issue_tracker.report_url,
+                &issue_tracker_path,
+                REPORT_URL_KEY,
+                UrlScope::IssueTrackers,
+            );
+        }
+    }
+}
+
+pub(crate) fn replace_deprecation_urls(update_center: &mut UpdateCenter, rewriter: &UrlRewriter) {
+    for (plugin_name, deprecation) in update_center.deprecations.iter_mut().flatten() {
+        let deprecation_path = format!("{}.{}", DEPRECATIONS_KEY, plugin_name);
+
+        rewriter.rewrite_field(
+            &mut deprecation.url,
+            &deprecation_path,
+            URL_KEY,
+            UrlScope::Deprecations,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::UrlRewriteRuleConfig;
+
+    // an empty `url_replace_into` leaves out the literal replacement
+    fn site(url_replace_into: &str, rules: &[(&str, &str, Option<&str>)]) -> SiteConfig {
+        let url_replace_from = if url_replace_into.is_empty() {
+            ""
+        } else {
+            "https://updates.jenkins.io/download/"
+        };
+
+        SiteConfig {
+            name: "test".to_owned(),
+            update_center_url: "https://updates.jenkins.io/update-center.json".to_owned(),
+            suppress_front: None,
+            suppress_back: None,
+            connection_check_url_change: String::new(),
+            url_replace_from: url_replace_from.to_owned(),
+            url_replace_into: url_replace_into.to_owned(),
+            url_rewrite_rules: rules
+                .iter()
+                .map(|&(pattern, replacement, scope)| UrlRewriteRuleConfig {
+                    pattern: pattern.to_owned(),
+                    replacement: replacement.to_owned(),
+                    scope: scope.map(str::to_owned),
+                })
+                .collect(),
+            apply_all_url_rewrite_rules: false,
+            modified_json_file_path: "update-center.json".into(),
+        }
+    }
+
+    const PLUGIN_URL: &str = "https://updates.jenkins.io/download/plugins/git/5.0/git.hpi";
+
+    #[test]
+    fn applies_the_first_matching_rule() {
+        let site = site("https://mirror/", &[("mirror", "second", None)]);
+        let rewriter = UrlRewriter::new(&site).unwrap();
+
+        assert_eq!(
+            rewriter.rewrite("url", PLUGIN_URL, UrlScope::Plugins),
+            "https://mirror/plugins/git/5.0/git.hpi"
+        );
+    }
+
+    #[test]
+    fn applies_all_matching_rules_in_order() {
+        let mut site = site("https://mirror/", &[("mirror", "second", None)]);
+        site.apply_all_url_rewrite_rules = true;
+        let rewriter = UrlRewriter::new(&site).unwrap();
+
+        assert_eq!(
+            rewriter.rewrite("url", PLUGIN_URL, UrlScope::Plugins),
+            "https://second/plugins/git/5.0/git.hpi"
+        );
+    }
+
+    #[test]
+    fn only_applies_rules_of_the_scope() {
+        let site = site(
+            "",
+            &[
+                ("^https://github.com/", "https://git.mirror/", Some("scm")),
+                ("^https://", "http://", Some("wiki")),
+            ],
+        );
+        let rewriter = UrlRewriter::new(&site).unwrap();
+
+        assert!(rewriter.has_scope(UrlScope::Scm));
+        assert!(!rewriter.has_scope(UrlScope::Plugins));
+        assert_eq!(
+            rewriter.rewrite("scm", "https://github.com/jenkinsci/git", UrlScope::Scm),
+            "https://git.mirror/jenkinsci/git"
+        );
+        assert_eq!(rewriter.rewrite("url", PLUGIN_URL, UrlScope::Plugins), PLUGIN_URL);
+    }
+
+    #[test]
+    fn replaces_capture_groups() {
+        let site = site(
+            "",
+            &[(
+                r"^https://updates\.jenkins\.io/download/plugins/(?P<name>[^/]+)/([^/]+)/",
+                "https://mirror/${name}-$2/",
+                Some("plugins"),
+            )],
+        );
+        let rewriter = UrlRewriter::new(&site).unwrap();
+
+        assert_eq!(
+            rewriter.rewrite("url", PLUGIN_URL, UrlScope::Plugins),
+            "https://mirror/git-5.0/git.hpi"
+        );
+    }
+
+    #[test]
+    fn replaces_into_literally() {
+        let site = site("https://mirror/$1/${name}/", &[]);
+        let rewriter = UrlRewriter::new(&site).unwrap();
+
+        assert_eq!(
+            rewriter.rewrite("url", PLUGIN_URL, UrlScope::Plugins),
+            "https://mirror/$1/${name}/plugins/git/5.0/git.hpi"
+        );
+        // the literal replacement is limited to download URLs
+        assert_eq!(rewriter.rewrite("wiki", PLUGIN_URL, UrlScope::Wiki), PLUGIN_URL);
+    }
+
+    #[test]
+    fn records_changes() {
+        let site = site("https://mirror/", &[]);
+        let rewriter = UrlRewriter::new(&site).unwrap();
+
+        rewriter.rewrite("plugins.git.url", PLUGIN_URL, UrlScope::Plugins);
+        rewriter.rewrite("core.url", "https://get.jenkins.io/war/jenkins.war", UrlScope::Core);
+
+        let changes = rewriter.into_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "plugins.git.url");
+        assert_eq!(changes[0].into, "https://mirror/plugins/git/5.0/git.hpi");
+    }
+}
@@ -0,0 +1,369 @@
+use config::{SigningConfig, VerificationConfig};
+use errors::*;
+use fetch::read_file_into_bytes;
+use model::*;
+use openssl::base64;
+use openssl::hash::{self, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::{Signer, Verifier};
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::{X509StoreContext, X509};
+use serde_json::{self, Value};
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        bail!(format!("Invalid hex string of length {}", hex.len()));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .chain_err(|| format!("Invalid hex digits '{}'", &hex[i..i + 2]))
+        })
+        .collect()
+}
+
+fn canonical_json(resp_json: &Value) -> Result<String> {
+    let resp_outer_map = match *resp_json {
+        Value::Object(ref resp_outer_map) => resp_outer_map,
+        ref c => bail!(format!(
+            "Expected outer most update-center JSON to be of Object type, but found content: {:?}",
+            c
+        )),
+    };
+
+    // the signature block is never part of what gets signed
+    let unsigned_map: MapStrVal = resp_outer_map
+        .iter()
+        .filter(|&(key, _)| key != SIGNATURE_KEY)
+        .map(|(key, value)| (key.to_owned(), value.clone()))
+        .collect();
+
+    let mut canonical = String::new();
+    write_canonical(&Value::Object(unsigned_map), &mut canonical);
+    Ok(canonical)
+}
+
+/// Writes `value` the way json-lib's `writeCanonical` does, which is what
+/// Jenkins computes the digest over: object keys sorted, no whitespace, and
+/// control characters always escaped as `\u00xx`, where serde_json would
+/// write `\n` and the like.
+fn write_canonical(value: &Value, canonical: &mut String) {
+    match *value {
+        Value::Object(ref map) => {
+            canonical.push('{');
+
+            // serde_json maps are already sorted by key
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i > 0 {
+                    canonical.push(',');
+                }
+
+                write_canonical_str(key, canonical);
+                canonical.push(':');
+                write_canonical(value, canonical);
+            }
+
+            canonical.push('}');
+        }
+        Value::Array(ref values) => {
+            canonical.push('[');
+
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    canonical.push(',');
+                }
+
+                write_canonical(value, canonical);
+            }
+
+            canonical.push(']');
+        }
+        Value::String(ref s) => write_canonical_str(s, canonical),
+        Value::Number(ref n) => {
+            let n = n.to_string();
+
+            // like Java, decimals lose their trailing zeros, down to `1` for `1.0`
+            if n.contains('.') && !n.contains('e') && !n.contains('E') {
+                canonical.push_str(n.trim_end_matches('0').trim_end_matches('.'));
+            } else {
+                canonical.push_str(&n);
+            }
+        }
+        Value::Bool(b) => canonical.push_str(if b { "true" } else { "false" }),
+        Value::Null => canonical.push_str("null"),
+    }
+}
+
+fn write_canonical_str(s: &str, canonical: &mut String) {
+    canonical.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                canonical.push('\\');
+                canonical.push(c);
+            }
+            c if c < ' ' => canonical.push_str(&format!("\\u{:04x}", c as u32)),
+            c => canonical.push(c),
+        }
+    }
+
+    canonical.push('"');
+}
+
+pub fn sign_update_center(update_center: &mut UpdateCenter, signing: &SigningConfig) -> Result<()> {
+    let private_key = {
+        let pem = read_file_into_bytes(&signing.private_key_file_path)?;
+
+        PKey::private_key_from_pem(&pem).chain_err(|| {
+            format!(
+                "Unable to parse private key PEM at {:?}",
+                signing.private_key_file_path
+            )
+        })?
+    };
+
+    let certificates = {
+        let pem = read_file_into_bytes(&signing.certificate_file_path)?;
+
+        X509::stack_from_pem(&pem).chain_err(|| {
+            format!(
+                "Unable to parse certificate chain PEM at {:?}",
+                signing.certificate_file_path
+            )
+        })?
+    };
+
+    if certificates.is_empty() {
+        bail!(format!(
+            "Expected at least one certificate in {:?}",
+            signing.certificate_file_path
+        ));
+    }
+
+    let encoded_certificates = certificates
+        .iter()
+        .map(|certificate| {
+            certificate
+                .to_der()
+                .map(|der| base64::encode_block(&der))
+                .chain_err(|| "Unable to encode certificate into DER form")
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let resp_json = serde_json::to_value(&*update_center)
+        .chain_err(|| "Unable to convert modified update-center into JSON for signing")?;
+
+    let canonical = canonical_json(&resp_json)?;
+
+    let digest = hash::hash(MessageDigest::sha1(), canonical.as_bytes())
+        .chain_err(|| "Unable to compute SHA-1 digest of canonical JSON")?;
+
+    let digest512 = hash::hash(MessageDigest::sha512(), canonical.as_bytes())
+        .chain_err(|| "Unable to compute SHA-512 digest of canonical JSON")?;
+
+    let sign_with = |message_digest: MessageDigest| -> Result<Vec<u8>> {
+        let mut signer = Signer::new(message_digest, &private_key)
+            .chain_err(|| "Unable to create signer from private key")?;
+
+        signer
+            .update(canonical.as_bytes())
+            .chain_err(|| "Unable to feed canonical JSON into signer")?;
+
+        signer
+            .sign_to_vec()
+            .chain_err(|| "Unable to sign canonical JSON")
+    };
+
+    let signature = sign_with(MessageDigest::sha1())?;
+    let signature512 = sign_with(MessageDigest::sha512())?;
+
+    update_center.signature = Some(Signature {
+        certificates: Some(encoded_certificates),
+        correct_digest: Some(base64::encode_block(&digest)),
+        correct_digest512: Some(to_hex(&digest512)),
+        correct_signature: Some(base64::encode_block(&signature)),
+        correct_signature512: Some(to_hex(&signature512)),
+        extra: MapStrVal::new(),
+    });
+
+    Ok(())
+}
+
+pub fn verify_update_center(resp_json: &Value, verification: &VerificationConfig) -> Result<()> {
+    // verification runs on the JSON as received, before any typed round trip
+    let signature = match resp_json.get(SIGNATURE_KEY) {
+        Some(signature) => signature,
+        None => bail!(format!(
+            "Unable to find '{}' for verifying upstream update-center",
+            SIGNATURE_KEY
+        )),
+    };
+
+    let signature: Signature = serde_json::from_value(signature.clone())
+        .chain_err(|| format!("Unable to parse '{}' of upstream update-center", SIGNATURE_KEY))?;
+
+    let certificates = signature
+        .certificates
+        .iter()
+        .flatten()
+        .map(|encoded_certificate| {
+            let der = base64::decode_block(encoded_certificate)
+                .chain_err(|| "Unable to decode base64 certificate")?;
+
+            X509::from_der(&der).chain_err(|| "Unable to parse DER certificate")
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (leaf, intermediates) = match certificates.split_first() {
+        Some(split) => split,
+        None => bail!(format!(
+            "Expected at least one certificate in '{}'",
+            SIGNATURE_KEY
+        )),
+    };
+
+    // check the certificate chain against the configured root CA bundle
+    let store = {
+        let pem = read_file_into_bytes(&verification.root_ca_file_path)?;
+
+        let root_cas = X509::stack_from_pem(&pem).chain_err(|| {
+            format!(
+                "Unable to parse root CA PEM at {:?}",
+                verification.root_ca_file_path
+            )
+        })?;
+
+        let mut store_builder =
+            X509StoreBuilder::new().chain_err(|| "Unable to create certificate store")?;
+
+        for root_ca in root_cas {
+            store_builder
+                .add_cert(root_ca)
+                .chain_err(|| "Unable to add root CA into certificate store")?;
+        }
+
+        store_builder.build()
+    };
+
+    let mut chain = Stack::new().chain_err(|| "Unable to create certificate stack")?;
+
+    for intermediate in intermediates {
+        chain
+            .push(intermediate.clone())
+            .chain_err(|| "Unable to push intermediate certificate into stack")?;
+    }
+
+    let mut context =
+        X509StoreContext::new().chain_err(|| "Unable to create certificate store context")?;
+
+    let chain_error = context
+        .init(&store, leaf, &chain, |c| {
+            c.verify_cert().map(|verified| {
+                if verified {
+                    None
+                } else {
+                    Some(c.error().to_string())
+                }
+            })
+        })
+        .chain_err(|| "Unable to verify certificate chain")?;
+
+    if let Some(chain_error) = chain_error {
+        bail!(format!(
+            "Certificate chain of upstream update-center failed verification: {}",
+            chain_error
+        ));
+    }
+
+    // check the digests and signatures over the canonical JSON
+    let canonical = canonical_json(resp_json)?;
+
+    let public_key = leaf
+        .public_key()
+        .chain_err(|| "Unable to obtain public key of signing certificate")?;
+
+    let verify_with = |message_digest: MessageDigest, signature: &[u8]| -> Result<bool> {
+        let mut verifier = Verifier::new(message_digest, &public_key)
+            .chain_err(|| "Unable to create verifier from public key")?;
+
+        verifier
+            .update(canonical.as_bytes())
+            .chain_err(|| "Unable to feed canonical JSON into verifier")?;
+
+        verifier
+            .verify(signature)
+            .chain_err(|| "Unable to verify signature of canonical JSON")
+    };
+
+    let mut checked_signature = false;
+
+    if let Some(ref digest) = signature.correct_digest {
+        let computed = hash::hash(MessageDigest::sha1(), canonical.as_bytes())
+            .chain_err(|| "Unable to compute SHA-1 digest of canonical JSON")?;
+
+        if base64::encode_block(&computed) != *digest {
+            bail!(format!(
+                "'{}' does not match canonical JSON",
+                CORRECT_DIGEST_KEY
+            ));
+        }
+    }
+
+    if let Some(ref digest512) = signature.correct_digest512 {
+        let computed = hash::hash(MessageDigest::sha512(), canonical.as_bytes())
+            .chain_err(|| "Unable to compute SHA-512 digest of canonical JSON")?;
+
+        if !to_hex(&computed).eq_ignore_ascii_case(digest512) {
+            bail!(format!(
+                "'{}' does not match canonical JSON",
+                CORRECT_DIGEST512_KEY
+            ));
+        }
+    }
+
+    if let Some(ref signature) = signature.correct_signature {
+        let signature = base64::decode_block(signature)
+            .chain_err(|| format!("Unable to decode base64 '{}'", CORRECT_SIGNATURE_KEY))?;
+
+        if !verify_with(MessageDigest::sha1(), &signature)? {
+            bail!(format!(
+                "'{}' does not match canonical JSON",
+                CORRECT_SIGNATURE_KEY
+            ));
+        }
+
+        checked_signature = true;
+    }
+
+    if let Some(ref signature512) = signature.correct_signature512 {
+        let signature512 = from_hex(signature512)
+            .chain_err(|| format!("Unable to decode hex '{}'", CORRECT_SIGNATURE512_KEY))?;
+
+        if !verify_with(MessageDigest::sha512(), &signature512)? {
+            bail!(format!(
+                "'{}' does not match canonical JSON",
+                CORRECT_SIGNATURE512_KEY
+            ));
+        }
+
+        checked_signature = true;
+    }
+
+    if !checked_signature {
+        bail!(format!(
+            "Expected either '{}' or '{}' to be present for verification",
+            CORRECT_SIGNATURE_KEY,
+            CORRECT_SIGNATURE512_KEY
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use config::ModConfig;
+use errors::*;
+use fetch::{read_file_into_string, Validators};
+use openssl::hash::{self, MessageDigest};
+use publish::PendingOutputs;
+use serde_json::{self, Value};
+use signature::to_hex;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// What the last run of jenkins-mod saw upstream, so that runs where
+/// nothing changed can leave the outputs alone.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ModState {
+    /// Digest of the config the outputs were last written with, since a
+    /// changed config calls for new outputs even if upstream did not change.
+    pub config_digest: Option<String>,
+    #[serde(default)]
+    pub sites: BTreeMap<String, SiteState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SiteState {
+    #[serde(default)]
+    pub validators: Validators,
+    pub generation_timestamp: Option<Value>,
+}
+
+impl ModState {
+    /// Reads the state file, where a missing file means there was no
+    /// previous run.
+    pub fn load(state_file_path: &Path) -> Result<ModState> {
+        if !state_file_path.exists() {
+            return Ok(ModState::default());
+        }
+
+        let state_str = read_file_into_string(state_file_path)?;
+
+        serde_json::from_str(&state_str)
+            .chain_err(|| format!("Unable to parse state file at {:?}", state_file_path))
+    }
+
+    pub fn save(&self, state_file_path: &Path) -> Result<()> {
+        let state_json = serde_json::to_string_pretty(self)
+            .chain_err(|| "Unable to convert state into pretty JSON form")?;
+
+        let mut outputs = PendingOutputs::new(false, None);
+        outputs.write(state_file_path, state_json.as_bytes())?;
+        outputs.commit()
+    }
+}
+
+pub fn config_digest(config: &ModConfig) -> Result<String> {
+    let config_json = serde_json::to_string(config)
+        .chain_err(|| "Unable to convert config into JSON form for its digest")?;
+
+    hash::hash(MessageDigest::sha256(), config_json.as_bytes())
+        .map(|digest| to_hex(&digest))
+        .chain_err(|| "Unable to compute SHA-256 digest of config")
+}
@@ -0,0 +1,886 @@
+use config::SyncConfig;
+use errors::*;
+use fetch::{new_client, read_file_into_string, Validators};
+use futures::Future;
+use futures_cpupool::CpuPool;
+use generation::Generation;
+use hyper::header::{ByteRangeSpec, ContentLength, Headers, Range};
+use hyper::status::StatusCode;
+use model::UrlEntry;
+use publish::promote_staged;
+use retry::{io_error, send_request, with_retries};
+use openssl::base64;
+use openssl::sha::{Sha1, Sha256};
+use serde_json;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+use walkdir::WalkDir;
+
+// a download in progress, renamed into place once complete and verified
+const PART_EXT: &str = "part";
+
+// validators of the response a partial file came from, for resuming it
+const PART_VALIDATORS_EXT: &str = "part.validators";
+
+/// A single artifact to mirror from `url` into `download_path`.
+#[derive(Clone)]
+pub struct SyncItem {
+    pub url: Url,
+    pub download_path: String,
+    /// What the partial download files are named after, which is outside of
+    /// the generation with generations.
+    pub partial_path: String,
+    pub url_entry: UrlEntry,
+}
+
+/// Artifacts to download, and previously mirrored files no longer listed.
+pub struct SyncPlan {
+    pub items: Vec<SyncItem>,
+    pub unused_paths: Vec<PathBuf>,
+    /// The fresh generation to download into, if generations are enabled.
+    pub generation: Option<Generation>,
+}
+
+fn remove_parents(dir_path: &Path) {
+    let res = fs::remove_dir(dir_path);
+
+    if res.is_ok() {
+        remove_parents(dir_path.parent().unwrap());
+    }
+}
+
+struct ChecksumHasher {
+    sha1: Sha1,
+    sha256: Sha256,
+}
+
+impl ChecksumHasher {
+    fn new() -> ChecksumHasher {
+        ChecksumHasher {
+            sha1: Sha1::new(),
+            sha256: Sha256::new(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.sha1.update(bytes);
+        self.sha256.update(bytes);
+    }
+
+    /// Checks against the strongest checksum available in the URL entry,
+    /// returning `None` if the entry carries no checksum at all.
+    fn verify(self, url_entry: &UrlEntry) -> Option<bool> {
+        match (&url_entry.sha256, &url_entry.sha1) {
+            (Some(sha256), _) => Some(base64::encode_block(&self.sha256.finish()) == *sha256),
+            (None, Some(sha1)) => Some(base64::encode_block(&self.sha1.finish()) == *sha1),
+            (None, None) => None,
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Result<ChecksumHasher> {
+    let mut file =
+        File::open(path).chain_err(|| format!("Unable to open file path at {:?}", path))?;
+
+    let mut hasher = ChecksumHasher::new();
+
+    // 512 KB
+    let mut file_bytes = vec![0; 512 * 1024];
+
+    loop {
+        let read_len = file
+            .read(&mut file_bytes)
+            .chain_err(|| format!("Unable to read file at {:?} for hashing", path))?;
+
+        if read_len == 0 {
+            break;
+        }
+
+        hasher.update(&file_bytes[..read_len]);
+    }
+
+    Ok(hasher)
+}
+
+// URL lists written before checksums were added only hold the bare URLs
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UrlListItem {
+    Url(String),
+    Entry(UrlEntry),
+}
+
+impl From<UrlListItem> for UrlEntry {
+    fn from(item: UrlListItem) -> UrlEntry {
+        match item {
+            UrlListItem::Url(url) => UrlEntry {
+                url,
+                sha1: None,
+                sha256: None,
+            },
+            UrlListItem::Entry(url_entry) => url_entry,
+        }
+    }
+}
+
+fn parse_url_list(urls_str: &str) -> serde_json::Result<Vec<UrlEntry>> {
+    let items: Vec<UrlListItem> = serde_json::from_str(urls_str)?;
+    Ok(items.into_iter().map(UrlEntry::from).collect())
+}
+
+pub fn read_url_list(url_list_json_file_path: &Path) -> Result<Vec<UrlEntry>> {
+    let urls_str = read_file_into_string(url_list_json_file_path)?;
+
+    parse_url_list(&urls_str).chain_err(|| {
+        format!(
+            "Error in parsing URL list from {:?}",
+            url_list_json_file_path
+        )
+    })
+}
+
+/// Maps every URL entry into a download path under `sync_root_dir_path`,
+/// and finds the files with accepted extensions that are no longer needed.
+/// With generations, the download paths are under a fresh generation
+/// instead, which simply leaves out the files no longer needed.
+pub fn plan_sync(config: &SyncConfig, url_entries: Vec<UrlEntry>) -> SyncPlan {
+    let generation = config
+        .generations
+        .as_ref()
+        .map(|_| Generation::new(&config.sync_root_dir_path));
+
+    let download_root_dir_path = match generation {
+        Some(ref generation) => &generation.dir_path,
+        None => &config.sync_root_dir_path,
+    };
+
+    let items: Vec<_> = url_entries
+        .into_iter()
+        .map(|url_entry| Url::parse(&url_entry.url).map(|url| (url, url_entry)))
+        .inspect(|url_res| {
+            // log any erroneous URL and continue
+            if let Err(e) = *url_res {
+                error!("Unable to parse into URL: {}", e);
+            }
+        })
+        .filter_map(|url_res| url_res.ok())
+        .map(|(url, url_entry)| {
+            let download_path = format!("{}{}", download_root_dir_path, url.path());
+
+            let partial_path = match generation {
+                Some(ref generation) => generation.partial_path(&download_path),
+                None => download_path.clone(),
+            };
+
+            SyncItem {
+                url,
+                download_path,
+                partial_path,
+                url_entry,
+            }
+        })
+        .collect();
+
+    // find all the existing paths for possible deletion for unused files
+    let sync_root_dir = Path::new(&config.sync_root_dir_path);
+
+    let filtered_paths: HashSet<_> = if generation.is_none() && sync_root_dir.is_dir() {
+        WalkDir::new(&config.sync_root_dir_path)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Ok(entry) => {
+                    let accepted = match completed_path(entry.path()).extension() {
+                        Some(ext) => config
+                            .accepted_file_exts
+                            .iter()
+                            .any(|accepted_file_ext| ext == accepted_file_ext.as_str()),
+                        None => false,
+                    };
+
+                    if accepted {
+                        Some(entry)
+                    } else {
+                        None
+                    }
+                }
+
+                Err(ref e) => {
+                    error!("Error in walking entry: {}", e);
+                    None
+                }
+            })
+            .map(|entry| -> PathBuf { entry.path().to_owned() })
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let to_download_paths: HashSet<_> = items
+        .iter()
+        .map(|item| PathBuf::from(&item.download_path))
+        .collect();
+
+    // partial files are kept as long as their artifact is still wanted
+    let unused_paths = filtered_paths
+        .into_iter()
+        .filter(|path| !to_download_paths.contains(&completed_path(path)))
+        .collect();
+
+    SyncPlan {
+        items,
+        unused_paths,
+        generation,
+    }
+}
+
+fn read_part_validators(part_validators_path: &str) -> Option<Validators> {
+    let validators_str = read_file_into_string(Path::new(part_validators_path)).ok()?;
+    serde_json::from_str(&validators_str).ok()
+}
+
+fn write_part_validators(part_validators_path: &str, validators: &Validators) -> Result<()> {
+    let validators_json = serde_json::to_string(validators)
+        .chain_err(|| "Unable to convert response validators into JSON form")?;
+
+    let mut validators_file = File::create(part_validators_path)
+        .chain_err(|| format!("Unable to create file at '{}'", part_validators_path))?;
+
+    validators_file
+        .write_all(validators_json.as_bytes())
+        .chain_err(|| format!("Unable to write into '{}'", part_validators_path))
+}
+
+fn remove_part_files(part_path: &str, part_validators_path: &str) {
+    for path in &[part_path, part_validators_path] {
+        if let Err(e) = fs::remove_file(path) {
+            if Path::new(path).exists() {
+                error!("Unable to remove partial file: {}", e);
+            }
+        }
+    }
+}
+
+/// The artifact a partial file belongs to, or the path itself otherwise.
+fn completed_path(path: &Path) -> PathBuf {
+    let path_str = path.to_string_lossy();
+
+    for ext in &[PART_VALIDATORS_EXT, PART_EXT] {
+        let suffix = format!(".{}", ext);
+
+        if path_str.ends_with(&suffix) {
+            return PathBuf::from(&path_str[..path_str.len() - suffix.len()]);
+        }
+    }
+
+    path.to_owned()
+}
+
+fn download_item(item: SyncItem) -> Result<()> {
+    let SyncItem {
+        url,
+        download_path,
+        partial_path,
+        url_entry,
+    } = item;
+
+    let url_str = format!("{}", url);
+
+    // existing file with matching checksum needs no download at all
+    if Path::new(&download_path).exists() {
+        let verified = hash_file(Path::new(&download_path))?.verify(&url_entry);
+
+        match verified {
+            Some(true) => {
+                info!(
+                    "Checksum of '{}' matches that of '{}', not downloading...",
+                    download_path,
+                    url_str
+                );
+
+                return Ok(());
+            }
+            Some(false) => info!(
+                "Checksum of '{}' does not match that of '{}', re-downloading...",
+                download_path,
+                url_str
+            ),
+            None => (),
+        }
+    }
+
+    let has_checksum = url_entry.sha1.is_some() || url_entry.sha256.is_some();
+
+    let part_path = format!("{}.{}", partial_path, PART_EXT);
+    let part_validators_path = format!("{}.{}", partial_path, PART_VALIDATORS_EXT);
+
+    // a partial file can only be resumed if it is known which response it
+    // came from, for the server to tell whether it is still the same
+    let mut resume = read_part_validators(&part_validators_path).and_then(|validators| {
+        let part_len = fs::metadata(&part_path).ok()?.len();
+
+        if part_len > 0 {
+            validators.if_range().map(|if_range| (part_len, if_range))
+        } else {
+            None
+        }
+    });
+
+    let mut headers = Headers::new();
+
+    if let Some((part_len, ref if_range)) = resume {
+        headers.set(Range::Bytes(vec![ByteRangeSpec::AllFrom(part_len)]));
+        headers.set(if_range.clone());
+    }
+
+    // performs HTTP request to get the file
+    let client = new_client()?;
+
+    let mut resp = send_request(client.get(url.clone()).headers(headers), &url_str)?;
+
+    // the partial file may already be complete, short of being renamed
+    if resp.status == StatusCode::RangeNotSatisfiable {
+        if hash_file(Path::new(&part_path))?.verify(&url_entry) == Some(true) {
+            info!(
+                "Partial file '{}' of '{}' is already complete",
+                part_path,
+                url_str
+            );
+
+            return complete_part_file(&part_path, &part_validators_path, &download_path);
+        }
+
+        info!(
+            "Unable to resume '{}' from '{}', downloading it again",
+            download_path,
+            url_str
+        );
+
+        remove_part_files(&part_path, &part_validators_path);
+        resume = None;
+        resp = send_request(client.get(url), &url_str)?;
+    }
+
+    // a full response means the artifact changed since
+    let resume_len = match (resume, resp.status) {
+        (Some((part_len, _)), StatusCode::PartialContent) => part_len,
+        (_, StatusCode::Ok) => 0,
+        (_, status) => bail!(
+            "HTTP request with URL '{}' failed with {}",
+            url_str,
+            status
+        ),
+    };
+
+    let content_len = match resp.headers.get::<ContentLength>() {
+        Some(content_len) => resume_len + content_len.0,
+        None => bail!("Unable to obtain HTTP response content length"),
+    };
+
+    // without checksum, fall back to check against existing file entry
+    // if present for similar content length
+    let found_file_len = if has_checksum {
+        None
+    } else {
+        let found_file_metadata = if Path::new(&download_path).exists() {
+            fs::metadata(&download_path).ok()
+        } else {
+            None
+        };
+
+        found_file_metadata.map(|meta| meta.len())
+    };
+
+    let same_content_opt = found_file_len.and_then(|file_len| {
+        if file_len == content_len {
+            Some(())
+        } else {
+            None
+        }
+    });
+
+    match same_content_opt {
+        Some(_) => info!(
+            "Content length {} of HTTP request '{}' \
+             same as file length of '{}', not downloading...",
+            content_len,
+            url_str,
+            download_path
+        ),
+        None => {
+            create_parent_dir(&part_path)?;
+
+            let (mut part_file, mut hasher) = if resume_len > 0 {
+                info!(
+                    "Resuming '{}' -> '{}' from byte {}",
+                    url_str,
+                    part_path,
+                    resume_len
+                );
+
+                let part_file = OpenOptions::new()
+                    .append(true)
+                    .open(&part_path)
+                    .chain_err(|| format!("Unable to open '{}' for resuming", part_path))?;
+
+                (part_file, hash_file(Path::new(&part_path))?)
+            } else {
+                info!("Downloading '{}' -> '{}'", url_str, part_path);
+
+                let part_file = File::create(&part_path).chain_err(|| {
+                    format!(
+                        "Unable to create file at '{}' for saving URL response",
+                        part_path
+                    )
+                })?;
+
+                write_part_validators(
+                    &part_validators_path,
+                    &Validators::from_headers(&resp.headers),
+                )?;
+
+                (part_file, ChecksumHasher::new())
+            };
+
+            let mut part_len = resume_len;
+
+            // 512 KB
+            let mut resp_bytes = [0; 512 * 1024];
+
+            loop {
+                let read_res = resp.read(&mut resp_bytes);
+
+                let read_len = match read_res {
+                    Ok(0) => break,
+                    Err(e) => {
+                        return Err(io_error(
+                            format!(
+                                "Download of '{}' interrupted after {} bytes, \
+                                 keeping '{}' to resume from",
+                                url_str,
+                                part_len,
+                                part_path
+                            ),
+                            &e,
+                        ))
+                    }
+                    Ok(len) => len,
+                };
+
+                hasher.update(&resp_bytes[..read_len]);
+
+                part_file
+                    .write_all(&resp_bytes[..read_len])
+                    .chain_err(|| {
+                        format!(
+                            "Unable to write bytes into download file path '{}'",
+                            part_path
+                        )
+                    })?;
+
+                part_len += read_len as u64;
+            }
+
+            if part_len < content_len {
+                bail!(ErrorKind::Transient(
+                    format!(
+                        "Download of '{}' ended after {} out of {} bytes, \
+                         keeping '{}' to resume from",
+                        url_str,
+                        part_len,
+                        content_len,
+                        part_path
+                    ),
+                    None
+                ));
+            }
+
+            drop(part_file);
+
+            if hasher.verify(&url_entry) == Some(false) {
+                // never leave a corrupted artifact around for Jenkins to pick up
+                remove_part_files(&part_path, &part_validators_path);
+
+                bail!(
+                    "Checksum of '{}' does not match that of '{}', file removed",
+                    part_path,
+                    url_str
+                );
+            }
+
+            complete_part_file(&part_path, &part_validators_path, &download_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn create_parent_dir(path: &str) -> Result<()> {
+    let parent_dir_path = match Path::new(path).parent() {
+        Some(parent_dir_path) => parent_dir_path,
+        None => bail!("Unable to get parent path of '{}'", path),
+    };
+
+    fs::create_dir_all(parent_dir_path)
+        .chain_err(|| format!("Unable to create directory chain {:?}", parent_dir_path))
+}
+
+fn complete_part_file(
+    part_path: &str,
+    part_validators_path: &str,
+    download_path: &str,
+) -> Result<()> {
+    create_parent_dir(download_path)?;
+
+    // replaces rather than overwrites any existing file, which may be
+    // hardlinked into an older generation
+    fs::rename(part_path, download_path)
+        .chain_err(|| format!("Unable to rename '{}' into '{}'", part_path, download_path))?;
+
+    let _ = fs::remove_file(part_validators_path);
+    Ok(())
+}
+
+/// Removes the unused files of `plan`, then downloads its items with
+/// `download_thread_count` threads. With generations, `current` is only
+/// switched over once every download has succeeded. With staging, the unused
+/// files are only removed once the staged update-center no longer refers to
+/// them.
+pub fn execute_sync(config: &SyncConfig, plan: SyncPlan) -> Result<()> {
+    let SyncPlan {
+        items,
+        unused_paths,
+        generation,
+    } = plan;
+
+    if let Some(ref generation) = generation {
+        let download_paths: Vec<_> = items
+            .iter()
+            .map(|item| item.download_path.as_str())
+            .collect();
+
+        generation.prepare(&download_paths)?;
+    }
+
+    // the live update-center may still refer to them until promotion
+    if config.staging_dir_path.is_none() {
+        remove_unused_paths(&unused_paths);
+    }
+
+    // starts the download process
+    let pool = CpuPool::new(config.download_thread_count as usize);
+
+    let download_futs: Vec<_> = items
+        .into_iter()
+        .map(|item| {
+            let retry = config.retry.clone();
+
+            pool.spawn_fn(move || {
+                let download_res = with_retries(retry.as_ref(), || download_item(item.clone()));
+
+                if let Err(ref e) = download_res {
+                    error!("Download error: {}", e);
+                }
+
+                download_res
+            })
+        })
+        .collect();
+
+    let download_count = download_futs.len();
+
+    let failed_count = download_futs
+        .into_iter()
+        .map(|download_fut| download_fut.wait())
+        .filter(|download_res| download_res.is_err())
+        .count();
+
+    if failed_count > 0 {
+        if let Some(generation) = generation {
+            generation.discard();
+        }
+
+        if config.staging_dir_path.is_some() {
+            warn!("Leaving the staged update-center unpublished");
+        }
+
+        bail!(
+            "{} out of {} downloads failed, see log for details",
+            failed_count,
+            download_count
+        );
+    }
+
+    if let (Some(generation), Some(generations)) = (generation, config.generations.as_ref()) {
+        generation.switch(generations.keep_count)?;
+    }
+
+    // every artifact is in place and verified, so Jenkins may now see them
+    if let Some(ref staging_dir_path) = config.staging_dir_path {
+        promote_staged(staging_dir_path)?;
+        remove_unused_paths(&unused_paths);
+    }
+
+    Ok(())
+}
+
+fn remove_unused_paths(unused_paths: &[PathBuf]) {
+    for unused_path in unused_paths {
+        let res = fs::remove_file(unused_path);
+
+        match res {
+            Ok(_) => {
+                info!("Remove unused file at {:?}", unused_path);
+
+                // continue to attempt to remove as much empty parent directories as possible
+                if let Some(parent_dir_path) = unused_path.parent() {
+                    remove_parents(parent_dir_path);
+                }
+            }
+            Err(e) => error!("Unable to remove unused file: {}", e),
+        }
+    }
+}
+
+/// Syncs every URL in the URL list of `config`.
+pub fn run_sync(config: &SyncConfig) -> Result<()> {
+    let url_list_json_file_path = match config.url_list_json_file_path {
+        Some(ref url_list_json_file_path) => url_list_json_file_path,
+        None => bail!("Expected 'url_list_json_file_path' to be present in sync config"),
+    };
+
+    let url_entries = read_url_list(url_list_json_file_path)?;
+    let plan = plan_sync(config, url_entries);
+    execute_sync(config, plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::process;
+    use std::thread;
+
+    #[test]
+    fn parses_url_list_of_entries() {
+        let url_entries = parse_url_list(
+            r#"[{"url": "http://mirror/a.hpi", "sha256": "abc="}, {"url": "http://mirror/b.hpi"}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(url_entries.len(), 2);
+        assert_eq!(url_entries[0].url, "http://mirror/a.hpi");
+        assert_eq!(url_entries[0].sha256.as_deref(), Some("abc="));
+        assert_eq!(url_entries[1].sha256, None);
+    }
+
+    #[test]
+    fn parses_url_list_of_bare_urls() {
+        let url_entries =
+            parse_url_list(r#"["http://mirror/a.hpi", "http://mirror/b.hpi"]"#).unwrap();
+
+        assert_eq!(url_entries.len(), 2);
+        assert_eq!(url_entries[1].url, "http://mirror/b.hpi");
+        assert!(url_entries[1].sha1.is_none() && url_entries[1].sha256.is_none());
+    }
+
+    const CONTENT: &[u8] = b"hello world";
+
+    // a download path under a fresh directory of its own
+    fn download_path(name: &str) -> String {
+        let dir_path = env::temp_dir().join(format!(
+            "jenkins-update-mod-sync-{}-{}",
+            name,
+            process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir_path);
+        fs::create_dir_all(&dir_path).unwrap();
+        dir_path.join("a.hpi").to_string_lossy().into_owned()
+    }
+
+    // answers each connection with the next canned response, returning the
+    // requests received once all responses are sent
+    fn serve(responses: Vec<Vec<u8>>) -> (Url, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/a.hpi", listener.local_addr().unwrap())).unwrap();
+
+        let handle = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut request = String::new();
+
+                    loop {
+                        let mut line = String::new();
+
+                        if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                            break;
+                        }
+
+                        request.push_str(&line.to_lowercase());
+                    }
+
+                    reader.get_mut().write_all(&response).unwrap();
+                    request
+                })
+                .collect()
+        });
+
+        (url, handle)
+    }
+
+    fn response(status: &str, headers: &[&str], body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+
+        response.push_str("\r\n");
+        let mut response = response.into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn item(url: Url, download_path: &str, content: &[u8]) -> SyncItem {
+        let mut hasher = ChecksumHasher::new();
+        hasher.update(content);
+
+        SyncItem {
+            url: url.clone(),
+            download_path: download_path.to_owned(),
+            partial_path: download_path.to_owned(),
+            url_entry: UrlEntry {
+                url: url.to_string(),
+                sha1: None,
+                sha256: Some(base64::encode_block(&hasher.sha256.finish())),
+            },
+        }
+    }
+
+    // a partial download of the first `len` bytes of the content
+    fn write_part(download_path: &str, len: usize) {
+        fs::write(format!("{}.{}", download_path, PART_EXT), &CONTENT[..len]).unwrap();
+
+        let validators = Validators {
+            etag: Some("\"v1\"".to_owned()),
+            last_modified: None,
+        };
+
+        write_part_validators(
+            &format!("{}.{}", download_path, PART_VALIDATORS_EXT),
+            &validators,
+        )
+        .unwrap();
+    }
+
+    fn assert_completed(download_path: &str) {
+        assert_eq!(fs::read(download_path).unwrap(), CONTENT);
+        assert!(!Path::new(&format!("{}.{}", download_path, PART_EXT)).exists());
+        assert!(!Path::new(&format!("{}.{}", download_path, PART_VALIDATORS_EXT)).exists());
+    }
+
+    #[test]
+    fn downloads_into_place() {
+        let download_path = download_path("fresh");
+        let (url, server) = serve(vec![response("200 OK", &[], CONTENT)]);
+
+        download_item(item(url, &download_path, CONTENT)).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("range:"));
+        assert_completed(&download_path);
+    }
+
+    #[test]
+    fn resumes_partial_downloads() {
+        let download_path = download_path("resume");
+        write_part(&download_path, 6);
+        let (url, server) = serve(vec![response("206 Partial Content", &[], &CONTENT[6..])]);
+
+        download_item(item(url, &download_path, CONTENT)).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("range: bytes=6-\r\n"));
+        assert!(requests[0].contains("if-range: \"v1\"\r\n"));
+        assert_completed(&download_path);
+    }
+
+    #[test]
+    fn restarts_on_full_responses() {
+        // the artifact changed since, so the server ignores the range
+        let download_path = download_path("restart");
+        write_part(&download_path, 6);
+        let (url, server) = serve(vec![response("200 OK", &["ETag: \"v2\""], CONTENT)]);
+
+        download_item(item(url, &download_path, CONTENT)).unwrap();
+
+        server.join().unwrap();
+        assert_completed(&download_path);
+    }
+
+    #[test]
+    fn completes_verified_parts_on_unsatisfiable_range() {
+        let download_path = download_path("complete");
+        write_part(&download_path, CONTENT.len());
+        let (url, server) = serve(vec![response("416 Range Not Satisfiable", &[], b"")]);
+
+        download_item(item(url, &download_path, CONTENT)).unwrap();
+
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_completed(&download_path);
+    }
+
+    #[test]
+    fn downloads_again_on_unsatisfiable_range() {
+        let download_path = download_path("again");
+        write_part(&download_path, 6);
+        let (url, server) = serve(vec![
+            response("416 Range Not Satisfiable", &[], b""),
+            response("200 OK", &[], CONTENT),
+        ]);
+
+        download_item(item(url, &download_path, CONTENT)).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(!requests[1].contains("range:"));
+        assert_completed(&download_path);
+    }
+
+    #[test]
+    fn fails_on_other_statuses() {
+        let download_path = download_path("status");
+        write_part(&download_path, 6);
+        let (url, server) = serve(vec![response("404 Not Found", &[], b"gone")]);
+
+        assert!(download_item(item(url, &download_path, CONTENT)).is_err());
+
+        server.join().unwrap();
+        assert!(!Path::new(&download_path).exists());
+    }
+
+    #[test]
+    fn removes_downloads_with_checksum_mismatch() {
+        let download_path = download_path("mismatch");
+        let (url, server) = serve(vec![response("200 OK", &[], b"hello there")]);
+
+        assert!(download_item(item(url, &download_path, CONTENT)).is_err());
+
+        server.join().unwrap();
+        assert!(!Path::new(&download_path).exists());
+        assert!(!Path::new(&format!("{}.{}", download_path, PART_EXT)).exists());
+        assert!(!Path::new(&format!("{}.{}", download_path, PART_VALIDATORS_EXT)).exists());
+    }
+}
@@ -0,0 +1,83 @@
+//! HTTPS for the hyper client over openssl, which hyper only brings along
+//! with its default features.
+
+use hyper;
+use hyper::net::{HttpStream, NetworkStream, SslClient};
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Wraps connections in TLS, verifying the server certificate and host
+/// name against the system trust store.
+pub(crate) struct OpensslClient {
+    connector: SslConnector,
+}
+
+impl OpensslClient {
+    pub(crate) fn new() -> Result<OpensslClient, ::openssl::error::ErrorStack> {
+        Ok(OpensslClient {
+            connector: SslConnector::builder(SslMethod::tls())?.build(),
+        })
+    }
+}
+
+impl SslClient for OpensslClient {
+    type Stream = TlsStream;
+
+    fn wrap_client(&self, stream: HttpStream, host: &str) -> hyper::Result<TlsStream> {
+        match self.connector.connect(host, stream) {
+            Ok(stream) => Ok(TlsStream(Arc::new(Mutex::new(stream)))),
+            Err(e) => Err(hyper::Error::Ssl(Box::new(io::Error::other(format!(
+                "TLS handshake with '{}' failed: {}",
+                host, e
+            ))))),
+        }
+    }
+}
+
+/// A TLS connection, shared as hyper clones the streams it pools.
+#[derive(Clone)]
+pub(crate) struct TlsStream(Arc<Mutex<SslStream<HttpStream>>>);
+
+impl TlsStream {
+    fn lock(&self) -> MutexGuard<'_, SslStream<HttpStream>> {
+        // a panic while holding the lock leaves nothing worth guarding
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+impl NetworkStream for TlsStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.lock().get_mut().peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.lock().get_ref().0.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.lock().get_ref().0.set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.lock().get_mut().close(how)
+    }
+}
@@ -0,0 +1,119 @@
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum VersionItem {
+    // declared first so that qualifiers such as 'beta' sort before any number
+    Qualifier(String),
+    Number(u64),
+}
+
+/// Jenkins-style version number, compared item by item where missing
+/// trailing items count as zero, so that `1.0 == 1.0.0` and `1.0-beta < 1.0`.
+#[derive(Debug, Clone)]
+pub struct VersionNumber {
+    items: Vec<VersionItem>,
+}
+
+impl VersionNumber {
+    pub fn parse(version: &str) -> VersionNumber {
+        let mut items = Vec::new();
+        let mut chars = version.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_digit() {
+                let mut digits = String::new();
+
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+
+                    digits.push(c);
+                    chars.next();
+                }
+
+                items.push(VersionItem::Number(digits.parse().unwrap_or(u64::MAX)));
+            } else if c.is_alphabetic() {
+                let mut qualifier = String::new();
+
+                while let Some(&c) = chars.peek() {
+                    if !c.is_alphabetic() {
+                        break;
+                    }
+
+                    qualifier.extend(c.to_lowercase());
+                    chars.next();
+                }
+
+                items.push(VersionItem::Qualifier(qualifier));
+            } else {
+                // separators such as '.', '-' and '_'
+                chars.next();
+            }
+        }
+
+        VersionNumber { items }
+    }
+}
+
+impl Ord for VersionNumber {
+    fn cmp(&self, other: &VersionNumber) -> Ordering {
+        let padding = VersionItem::Number(0);
+        let len = self.items.len().max(other.items.len());
+
+        (0..len)
+            .map(|i| {
+                let lhs = self.items.get(i).unwrap_or(&padding);
+                let rhs = other.items.get(i).unwrap_or(&padding);
+                lhs.cmp(rhs)
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for VersionNumber {
+    fn partial_cmp(&self, other: &VersionNumber) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for VersionNumber {
+    fn eq(&self, other: &VersionNumber) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VersionNumber {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> VersionNumber {
+        VersionNumber::parse(version)
+    }
+
+    #[test]
+    fn pads_missing_items_with_zero() {
+        assert_eq!(v("1.0"), v("1.0.0"));
+        assert_eq!(v("2"), v("2.0"));
+        assert!(v("1.0") < v("1.0.1"));
+    }
+
+    #[test]
+    fn compares_numbers_numerically() {
+        assert!(v("1.10") > v("1.2"));
+        assert!(v("2.361.4") > v("2.361.1"));
+        assert!(v("1.99") < v("2.0"));
+    }
+
+    #[test]
+    fn sorts_qualifiers_before_numbers() {
+        assert!(v("1.0-beta") < v("1.0"));
+        assert!(v("1.0-beta") < v("1.0.1"));
+        assert!(v("1.0-alpha") < v("1.0-beta"));
+        assert!(v("1.0-beta-2") > v("1.0-beta-1"));
+        assert_eq!(v("1.0-BETA"), v("1.0-beta"));
+    }
+}
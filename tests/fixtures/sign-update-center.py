@@ -0,0 +1,105 @@
+#!/usr/bin/env python3
+# Regenerates update-center.json, signed over the canonical form written by
+# json-lib's `writeCanonical`, which is what Jenkins verifies against. Kept
+# independent of the Rust code so that the tests are not checked against
+# themselves.
+#
+#   ./sign-update-center.py > update-center.json
+
+import base64
+import collections
+import hashlib
+import json
+import subprocess
+import sys
+
+
+def quote_canonical(s):
+    # JSONUtils.quoteCanonical: only `"`, `\` and control characters are
+    # escaped, the latter always as \u00xx
+    out = ['"']
+    for c in s:
+        if c in '"\\':
+            out.append('\\' + c)
+        elif c < ' ':
+            out.append('\\u%04x' % ord(c))
+        else:
+            out.append(c)
+    out.append('"')
+    return ''.join(out)
+
+
+def write_canonical(v):
+    if isinstance(v, dict):
+        return '{' + ','.join(
+            quote_canonical(k) + ':' + write_canonical(v[k]) for k in sorted(v)) + '}'
+    if isinstance(v, list):
+        return '[' + ','.join(write_canonical(x) for x in v) + ']'
+    if isinstance(v, str):
+        return quote_canonical(v)
+    if v is True:
+        return 'true'
+    if v is False:
+        return 'false'
+    if v is None:
+        return 'null'
+    return str(v)
+
+
+def quote_upstream(s):
+    # JSONUtils.quote, which the upstream files are written with
+    out = ['"']
+    prev = ''
+    for c in s:
+        if c in '"\\':
+            out.append('\\' + c)
+        elif c == '/' and prev == '<':
+            out.append('\\/')
+        elif c in '\b\t\n\f\r':
+            out.append({'\b': '\\b', '\t': '\\t', '\n': '\\n', '\f': '\\f', '\r': '\\r'}[c])
+        elif c < ' ':
+            out.append('\\u%04x' % ord(c))
+        else:
+            out.append(c)
+        prev = c
+    out.append('"')
+    return ''.join(out)
+
+
+def write_upstream(v):
+    if isinstance(v, dict):
+        return '{' + ','.join(
+            quote_upstream(k) + ':' + write_upstream(x) for k, x in v.items()) + '}'
+    if isinstance(v, list):
+        return '[' + ','.join(write_upstream(x) for x in v) + ']'
+    if isinstance(v, str):
+        return quote_upstream(v)
+    return write_canonical(v)
+
+
+def sign(digest, data):
+    return subprocess.run(
+        ['openssl', 'dgst', '-' + digest, '-sign', 'signer.key'],
+        input=data, stdout=subprocess.PIPE, check=True).stdout
+
+
+def der(pem_file):
+    return subprocess.run(
+        ['openssl', 'x509', '-in', pem_file, '-outform', 'DER'],
+        stdout=subprocess.PIPE, check=True).stdout
+
+
+uc = json.load(open('update-center.unsigned.json'), object_pairs_hook=collections.OrderedDict)
+uc.pop('signature', None)
+
+canonical = write_canonical(uc).encode('utf-8')
+
+uc['signature'] = collections.OrderedDict([
+    ('certificates', [base64.b64encode(der('signer.crt')).decode()]),
+    ('correct_digest', base64.b64encode(hashlib.sha1(canonical).digest()).decode()),
+    ('correct_digest512', hashlib.sha512(canonical).hexdigest()),
+    ('correct_signature', base64.b64encode(sign('sha1', canonical)).decode()),
+    ('correct_signature512', sign('sha512', canonical).hex()),
+])
+
+sys.stdout.write('updateCenter.post(\n' + write_upstream(uc) + '\n);')
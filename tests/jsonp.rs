@@ -0,0 +1,61 @@
+//! Detection of the envelope around update-center JSON, which is written back
+//! out unchanged.
+
+extern crate jenkins_update_mod;
+#[macro_use]
+extern crate serde_json;
+
+use jenkins_update_mod::jsonp::parse_jsonp;
+
+const JSON_STR: &str = r#"{"id":"default","plugins":{}}"#;
+
+fn assert_parses(resp_str: &str, prefix: &str, suffix: &str) {
+    let (resp_json, envelope) = parse_jsonp(resp_str).unwrap();
+
+    assert_eq!(resp_json, json!({"id": "default", "plugins": {}}));
+    assert_eq!(envelope.prefix, prefix);
+    assert_eq!(envelope.suffix, suffix);
+    assert_eq!(envelope.wrap(JSON_STR), resp_str);
+}
+
+#[test]
+fn parses_update_center_post() {
+    assert_parses(
+        &format!("updateCenter.post({});", JSON_STR),
+        "updateCenter.post(",
+        ");",
+    );
+}
+
+#[test]
+fn parses_padding_with_whitespace_and_newlines() {
+    assert_parses(
+        &format!("\nupdateCenter.post(\n{}\n);\n", JSON_STR),
+        "\nupdateCenter.post(\n",
+        "\n);\n",
+    );
+
+    assert_parses(
+        &format!("  updateCenter . post (\r\n\t{}\t)  ", JSON_STR),
+        "  updateCenter . post (\r\n\t",
+        "\t)  ",
+    );
+}
+
+#[test]
+fn parses_other_callback_names() {
+    assert_parses(&format!("foo.bar({});", JSON_STR), "foo.bar(", ");");
+    assert_parses(&format!("$_cb1({})", JSON_STR), "$_cb1(", ")");
+}
+
+#[test]
+fn parses_plain_json() {
+    assert_parses(JSON_STR, "", "");
+    assert_parses(&format!("\n {}\n", JSON_STR), "\n ", "\n");
+}
+
+#[test]
+fn rejects_content_without_json() {
+    assert!(parse_jsonp("<html>Not Found</html>").is_err());
+    assert!(parse_jsonp(&format!("updateCenter.post({}", JSON_STR)).is_err());
+}
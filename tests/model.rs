@@ -0,0 +1,44 @@
+//! Round-trips through the typed model, which must leave everything it does
+//! not modify exactly as upstream wrote it.
+
+extern crate jenkins_update_mod;
+#[macro_use]
+extern crate serde_json;
+
+use jenkins_update_mod::jsonp::parse_jsonp;
+use jenkins_update_mod::model::{UpdateCenter, Warning};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+fn fixture_path(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(file_name)
+}
+
+#[test]
+fn update_center_round_trips() {
+    let resp_str = fs::read_to_string(fixture_path("update-center.json")).unwrap();
+    let resp_json = parse_jsonp(&resp_str).unwrap().0;
+
+    let update_center: UpdateCenter = serde_json::from_value(resp_json.clone()).unwrap();
+    let round_tripped: Value = serde_json::to_value(&update_center).unwrap();
+
+    assert_eq!(round_tripped, resp_json);
+}
+
+#[test]
+fn warning_without_versions_round_trips() {
+    let warning_json = json!({
+        "id": "SECURITY-1",
+        "message": "Withdrawn",
+        "name": "ant",
+        "type": "plugin",
+        "url": "https://www.jenkins.io/security/advisory/"
+    });
+
+    let warning: Warning = serde_json::from_value(warning_json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&warning).unwrap(), warning_json);
+}
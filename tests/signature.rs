@@ -0,0 +1,75 @@
+//! Signature checks against `fixtures/update-center.json`, an update-center
+//! in the upstream format whose strings contain control characters, signed
+//! by `fixtures/sign-update-center.py` over json-lib's canonical form.
+
+extern crate jenkins_update_mod;
+extern crate serde_json;
+
+use jenkins_update_mod::config::{SigningConfig, VerificationConfig};
+use jenkins_update_mod::jsonp::parse_jsonp;
+use jenkins_update_mod::model::UpdateCenter;
+use jenkins_update_mod::signature::{sign_update_center, verify_update_center};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+fn fixture_path(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(file_name)
+}
+
+fn read_fixture_json() -> Value {
+    let resp_str = fs::read_to_string(fixture_path("update-center.json")).unwrap();
+    parse_jsonp(&resp_str).unwrap().0
+}
+
+fn verification() -> VerificationConfig {
+    VerificationConfig {
+        root_ca_file_path: fixture_path("root-ca.crt"),
+    }
+}
+
+#[test]
+fn verifies_upstream_update_center() {
+    verify_update_center(&read_fixture_json(), &verification()).unwrap();
+}
+
+#[test]
+fn rejects_tampered_update_center() {
+    let mut resp_json = read_fixture_json();
+    resp_json["plugins"]["ant"]["excerpt"] = Value::from("Adds Apache Ant support to Jenkins.");
+
+    assert!(verify_update_center(&resp_json, &verification()).is_err());
+}
+
+#[test]
+fn re_signed_update_center_verifies() {
+    let resp_json = read_fixture_json();
+    let mut update_center: UpdateCenter = serde_json::from_value(resp_json.clone()).unwrap();
+
+    let signing = SigningConfig {
+        private_key_file_path: fixture_path("signer.key"),
+        certificate_file_path: fixture_path("signer.crt"),
+    };
+
+    sign_update_center(&mut update_center, &signing).unwrap();
+
+    // same content as upstream, so the digests must agree with its signer
+    let signature = update_center.signature.as_ref().unwrap();
+    let upstream_signature = &resp_json["signature"];
+
+    assert_eq!(
+        signature.correct_digest.as_ref().unwrap(),
+        upstream_signature["correct_digest"].as_str().unwrap()
+    );
+
+    assert_eq!(
+        signature.correct_digest512.as_ref().unwrap(),
+        upstream_signature["correct_digest512"].as_str().unwrap()
+    );
+
+    let signed_json = serde_json::to_value(&update_center).unwrap();
+    verify_update_center(&signed_json, &verification()).unwrap();
+}